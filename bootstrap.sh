@@ -12,6 +12,28 @@ set -euo pipefail
 ZIRCON_REPO="https://github.com/zirco-lang/zircon.git"
 ZIRCON_REF="${1:-nightly}"
 
+# Function to find the Zircon root the same way Zircon does: ZIRCON_PREFIX if
+# set, then the XDG data directory (except on macOS, and unless only
+# ~/.zircon exists), then ~/.zircon
+detect_zircon_root() {
+    if [[ -n "${ZIRCON_PREFIX:-}" ]]; then
+        echo "${ZIRCON_PREFIX/#\~/$HOME}"
+        return
+    fi
+
+    local legacy_root="$HOME/.zircon"
+    if [[ "$(uname -s)" != "Darwin" ]]; then
+        local xdg_root="${XDG_DATA_HOME:-$HOME/.local/share}/zircon"
+        if [[ -d "$xdg_root" || ! -d "$legacy_root" ]]; then
+            echo "$xdg_root"
+            return
+        fi
+    fi
+    echo "$legacy_root"
+}
+
+ZIRCON_ROOT="$(detect_zircon_root)"
+
 # Function to detect platform and architecture
 detect_platform_arch() {
     local os
@@ -83,16 +105,16 @@ try_install_prebuilt() {
             echo "✓ Successfully extracted prebuilt zircon"
             
             # Move the extracted contents to self directory
-            mkdir -p "$HOME/.zircon"
-            rm -rf "$HOME/.zircon/self"
-            mv "$temp_extract_dir" "$HOME/.zircon/self"
+            mkdir -p "$ZIRCON_ROOT"
+            rm -rf "$ZIRCON_ROOT/self"
+            mv "$temp_extract_dir" "$ZIRCON_ROOT/self"
             
             # Make the binary executable
-            chmod +x "$HOME/.zircon/self/bin/zircon"
+            chmod +x "$ZIRCON_ROOT/self/bin/zircon"
             
             # Create the bin directory and symlink
-            mkdir -p "$HOME/.zircon/bin"
-            ln -sf "$HOME/.zircon/self/bin/zircon" "$HOME/.zircon/bin/zircon"
+            mkdir -p "$ZIRCON_ROOT/bin"
+            ln -sf "$ZIRCON_ROOT/self/bin/zircon" "$ZIRCON_ROOT/bin/zircon"
             
             # Clean up
             rm -f "$temp_file"
@@ -129,9 +151,15 @@ if ! command -v rustc &>/dev/null; then
 fi
 echo "Rust found: $(rustc --version)"
 
-if [[ -d "$HOME/.zircon" ]]; then
-    echo "Removing existing ~/.zircon directory to allow for a fresh install..."
-    rm -rf "$HOME/.zircon"
+if [[ "$ZIRCON_ROOT" != /* || "$ZIRCON_ROOT" == "/" || "$ZIRCON_ROOT" == "$HOME" ]]; then
+    echo "Error: refusing to install into '$ZIRCON_ROOT'. Set ZIRCON_PREFIX to a dedicated absolute path."
+    exit 1
+fi
+echo "Installing Zircon into $ZIRCON_ROOT"
+
+if [[ -d "$ZIRCON_ROOT" ]]; then
+    echo "Removing existing $ZIRCON_ROOT directory to allow for a fresh install..."
+    rm -rf "$ZIRCON_ROOT"
 fi
 
 # Try to install prebuilt binary first
@@ -142,8 +170,8 @@ else
     echo ""
     echo "Building zircon from source..."
     
-    mkdir -p "$HOME/.zircon/sources/zirco-lang"
-    cd "$HOME/.zircon/sources/zirco-lang"
+    mkdir -p "$ZIRCON_ROOT/sources/zirco-lang"
+    cd "$ZIRCON_ROOT/sources/zirco-lang"
     
     # Clone the Zircon repository
     echo "Downloading Zircon source code..."
@@ -169,15 +197,15 @@ else
     fi
     
     # Create symlink from self to sources/zirco-lang/zircon
-    if ! ln -sf "$HOME/.zircon/sources/zirco-lang/zircon" "$HOME/.zircon/self"; then
+    if ! ln -sf "$ZIRCON_ROOT/sources/zirco-lang/zircon" "$ZIRCON_ROOT/self"; then
         echo "Error: ln -sf (self symlink) failed with exit code $?"
         exit 1
     fi
     
-    # Create a symlink to the zircon binary in ~/.zircon/bin
-    mkdir -p "$HOME/.zircon/bin"
-    # ~/.zircon/bin/zircon is managed by this script. Later there will be other files in ~/.zircon/bin that Zircon itself manages.
-    if ! ln -sf "$HOME/.zircon/sources/zirco-lang/zircon/target/release/zircon" "$HOME/.zircon/bin/zircon"; then
+    # Create a symlink to the zircon binary in the root's bin directory
+    mkdir -p "$ZIRCON_ROOT/bin"
+    # bin/zircon is managed by this script. Later there will be other files in bin that Zircon itself manages.
+    if ! ln -sf "$ZIRCON_ROOT/sources/zirco-lang/zircon/target/release/zircon" "$ZIRCON_ROOT/bin/zircon"; then
         echo "Error: ln -sf (bin symlink) failed with exit code $?"
         exit 1
     fi
@@ -185,11 +213,12 @@ fi
 
 # This only adds to PATH for the duration of this script.
 # Users will later be instructed to add this to their shell profile.
-export PATH="$HOME/.zircon/bin:$PATH"
+export PATH="$ZIRCON_ROOT/bin:$PATH"
 
 # Run the bootstrap command
 echo ""
-if ! zircon _ bootstrap; then
+# Point Zircon at the root the binary was installed into
+if ! ZIRCON_PREFIX="$ZIRCON_ROOT" zircon _ bootstrap; then
     echo "Error: zircon _ bootstrap failed with exit code $?"
     exit 1
 fi
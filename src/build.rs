@@ -1,39 +1,405 @@
 //! Build operations for compiling Rust projects
 
-use std::{path::Path, process::Command};
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use owo_colors::AnsiColors;
+use terminal_size::Width;
+
+use crate::deps;
+use crate::output::{self, Stream, status, verbose};
+
+/// Name of the file in a toolchain directory that the build output is saved to
+pub const BUILD_LOG_FILE: &str = "build.log";
+
+/// Environment variable with the comma-separated cargo features to build zrc
+/// with
+pub const FEATURES_ENV_VAR: &str = "ZIRCON_CARGO_FEATURES";
+
+/// Environment variable that disables zrc's default cargo features when set
+/// to `1`
+pub const NO_DEFAULT_FEATURES_ENV_VAR: &str = "ZIRCON_NO_DEFAULT_FEATURES";
+
+/// Run a command, streaming its output to the console and saving it to `log_path`
+///
+/// Each line on the console is prefixed with `[<prefix>]` (e.g. `[cargo]`) to
+/// set it apart from Zircon's own messages. With `--quiet` nothing is printed,
+/// and the output only goes to the log. Standard output and standard error are
+/// kept apart on the console and interleaved in the log a line at a time.
+pub fn run_logged(command: &mut Command, prefix: &str, log_path: &Path) -> io::Result<ExitStatus> {
+    let log = Arc::new(Mutex::new(File::create(log_path)?));
+    let label = format!("[{}]", prefix);
+    let show_output = output::show_status();
+    if show_output {
+        keep_cargo_terminal_output(command, label.len() + 1);
+    }
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take().map(|pipe| {
+        let mut splitter = OutputSplitter::new(
+            show_output.then(io::stdout),
+            console_prefix(&label, Stream::Stdout),
+            Arc::clone(&log),
+        );
+        thread::spawn(move || splitter.copy_from(pipe))
+    });
+    let stderr = child.stderr.take().map(|pipe| {
+        let mut splitter = OutputSplitter::new(
+            show_output.then(io::stderr),
+            console_prefix(&label, Stream::Stderr),
+            Arc::clone(&log),
+        );
+        thread::spawn(move || splitter.copy_from(pipe))
+    });
+
+    let status = child.wait()?;
+    for handle in [stdout, stderr].into_iter().flatten() {
+        handle
+            .join()
+            .map_err(|_| io::Error::other("output thread panicked"))??;
+    }
+
+    Ok(status)
+}
+
+/// Get the prefix for console lines written to `stream`
+fn console_prefix(label: &str, stream: Stream) -> String {
+    format!("{} ", output::paint(label, AnsiColors::Cyan, stream))
+}
+
+/// Ask cargo for the progress bar and colors it shows on a terminal, which it
+/// leaves out when its output is piped
+///
+/// This only happens when stderr is a terminal, and settings already in the
+/// environment are left alone. The progress bar is narrowed by `prefix_width`
+/// so prefixed lines don't wrap.
+fn keep_cargo_terminal_output(command: &mut Command, prefix_width: usize) {
+    let stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return;
+    }
+    let is_set = |command: &Command, key: &str| {
+        std::env::var_os(key).is_some() || command.get_envs().any(|(k, _)| k == key)
+    };
+
+    if output::use_color(Stream::Stderr) && !is_set(command, "CARGO_TERM_COLOR") {
+        command.env("CARGO_TERM_COLOR", "always");
+    }
+    if !is_set(command, "CARGO_TERM_PROGRESS_WHEN")
+        && let Some((Width(width), _)) = terminal_size::terminal_size_of(&stderr)
+    {
+        // Cargo needs a width to draw the progress bar when it isn't on a terminal
+        command.env("CARGO_TERM_PROGRESS_WHEN", "always").env(
+            "CARGO_TERM_PROGRESS_WIDTH",
+            usize::from(width).saturating_sub(prefix_width).to_string(),
+        );
+    }
+}
+
+/// Copies a child's output pipe to a console stream and the shared log file
+///
+/// Console lines start with the prefix. Output is passed on as soon as it is
+/// read rather than a line at a time, so prompts and progress bars aren't held
+/// back. A lone `\r` (used to redraw progress lines) also starts a new
+/// prefixed line. A `\r` at the end of one read is held until the next, in
+/// case it is the first half of a `\r\n`.
+///
+/// The log gets whole lines as they end up on a terminal: without color codes,
+/// and without progress lines that were redrawn.
+struct OutputSplitter<W> {
+    /// Where to print the output, or `None` under `--quiet`
+    console: Option<W>,
+    /// Printed at the start of each console line
+    prefix: String,
+    /// The log file, shared with the child's other output stream
+    log: Arc<Mutex<File>>,
+    /// Whether the next byte printed starts a console line
+    at_line_start: bool,
+    /// Whether the last byte read was a `\r` that hasn't been printed yet
+    pending_cr: bool,
+    /// The current line, as it goes to the log
+    log_line: Vec<u8>,
+    /// How far into an ANSI escape sequence the log line is
+    escape: AnsiEscape,
+}
+
+/// Progress through an ANSI escape sequence, which is left out of the log
+#[derive(Clone, Copy)]
+enum AnsiEscape {
+    /// Not in an escape sequence
+    Outside,
+    /// Just after the escape character
+    Started,
+    /// In a control sequence (`ESC [`), which ends with a byte from `@` to `~`
+    Control,
+}
+
+impl<W: Write> OutputSplitter<W> {
+    /// Create a splitter that prints to `console` (unless it is `None`) and
+    /// writes to `log`
+    const fn new(console: Option<W>, prefix: String, log: Arc<Mutex<File>>) -> Self {
+        Self {
+            console,
+            prefix,
+            log,
+            at_line_start: true,
+            pending_cr: false,
+            log_line: Vec::new(),
+            escape: AnsiEscape::Outside,
+        }
+    }
+
+    /// Copy everything from `pipe` until it is closed
+    fn copy_from(&mut self, mut pipe: impl Read) -> io::Result<()> {
+        let mut buffer = [0; 8192];
+        loop {
+            let bytes_read = pipe.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            let mut printed = Vec::with_capacity(bytes_read + self.prefix.len());
+            for &byte in &buffer[..bytes_read] {
+                self.push(byte, &mut printed)?;
+            }
+            self.print(&printed)?;
+        }
+
+        // A `\r` held back at the end was a lone one
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.print(b"\r")?;
+            self.log_line.clear();
+        }
+        if !self.log_line.is_empty() {
+            self.end_log_line()?;
+        }
+        Ok(())
+    }
+
+    /// Handle one byte of output, adding what should be printed to `printed`
+    fn push(&mut self, byte: u8, printed: &mut Vec<u8>) -> io::Result<()> {
+        if std::mem::take(&mut self.pending_cr) {
+            if byte == b'\n' {
+                printed.extend_from_slice(b"\r\n");
+                self.at_line_start = true;
+                return self.end_log_line();
+            }
+            // The line is about to be redrawn, so it isn't logged
+            printed.push(b'\r');
+            self.at_line_start = true;
+            self.log_line.clear();
+            self.escape = AnsiEscape::Outside;
+        }
+        if byte == b'\r' {
+            self.pending_cr = true;
+            return Ok(());
+        }
+
+        if self.at_line_start {
+            printed.extend_from_slice(self.prefix.as_bytes());
+            self.at_line_start = false;
+        }
+        printed.push(byte);
+        if byte == b'\n' {
+            self.at_line_start = true;
+            return self.end_log_line();
+        }
+        self.escape = match (self.escape, byte) {
+            (AnsiEscape::Outside, 0x1b) => AnsiEscape::Started,
+            (AnsiEscape::Outside, _) => {
+                self.log_line.push(byte);
+                AnsiEscape::Outside
+            }
+            (AnsiEscape::Started, b'[') => AnsiEscape::Control,
+            // Other escape sequences are two bytes long
+            (AnsiEscape::Started, _) | (AnsiEscape::Control, b'@'..=b'~') => AnsiEscape::Outside,
+            (AnsiEscape::Control, _) => AnsiEscape::Control,
+        };
+        Ok(())
+    }
+
+    /// Print bytes to the console, if there is one
+    fn print(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if let Some(console) = &mut self.console
+            && !bytes.is_empty()
+        {
+            console.write_all(bytes)?;
+            console.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the current line to the log
+    fn end_log_line(&mut self) -> io::Result<()> {
+        self.log_line.push(b'\n');
+        self.log
+            .lock()
+            .map_err(|_| io::Error::other("build log lock poisoned"))?
+            .write_all(&self.log_line)?;
+        self.log_line.clear();
+        self.escape = AnsiEscape::Outside;
+        Ok(())
+    }
+}
+
+/// Get the cargo to run: the `--cargo` option if given, then the `CARGO`
+/// environment variable (as set by rustup and by cargo itself), then `cargo`
+/// from `PATH`
+pub fn cargo_program(cargo: Option<&Path>) -> PathBuf {
+    cargo.map_or_else(
+        || {
+            std::env::var_os("CARGO")
+                .filter(|cargo| !cargo.is_empty())
+                .map_or_else(|| PathBuf::from("cargo"), PathBuf::from)
+        },
+        Path::to_path_buf,
+    )
+}
 
 /// Build a Rust project using cargo
-pub fn build_rust_project(source_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Building (this may take several minutes)...");
+///
+/// The output is also saved to `log_path`.
+pub fn build_rust_project(
+    source_dir: &Path,
+    cargo: &Path,
+    log_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    status!("Building (this may take several minutes)...");
 
-    let status = Command::new("cargo")
-        .arg("build")
-        .arg("--release")
-        .current_dir(source_dir)
-        .status()?;
+    let status = run_logged(
+        Command::new(cargo)
+            .arg("build")
+            .arg("--release")
+            .current_dir(source_dir),
+        "cargo",
+        log_path,
+    )?;
 
     if !status.success() {
         let exit_code = status.code().unwrap_or(-1);
-        return Err(format!("Build failed (exit code: {})", exit_code).into());
+        return Err(format!(
+            "Build failed (exit code: {})\nSee the build log: {}",
+            exit_code,
+            log_path.display()
+        )
+        .into());
     }
 
-    println!("Build complete!");
+    status!("Build complete!");
     Ok(())
 }
 
-/// Check if cargo is available
-pub fn check_cargo() -> Result<(), Box<dyn std::error::Error>> {
-    let result = Command::new("cargo").arg("--version").output();
+/// Build zrc with cargo, without the repository's build hook
+///
+/// `profile` is `release` or `debug`, and `env` is passed to cargo (e.g.
+/// `CARGO_BUILD_JOBS`). Features are taken from [`FEATURES_ENV_VAR`] and
+/// [`NO_DEFAULT_FEATURES_ENV_VAR`] in `env`, as a hook would. The output is
+/// also saved to `log_path`. Returns the
+/// directory the binaries were written to, which follows `CARGO_TARGET_DIR`
+/// and the target triple in `CARGO_BUILD_TARGET` if `env` sets them or cargo
+/// inherits them from Zircon's environment.
+pub fn build_zrc(
+    source_dir: &Path,
+    profile: &str,
+    env: &[(String, String)],
+    log_path: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    status!("Building zrc with cargo (this may take several minutes)...");
+
+    // Like the hook, cargo follows a `CARGO` in `env`
+    let cargo = env_value(env, "CARGO").map_or_else(|| cargo_program(None), PathBuf::from);
+    let target = cargo_env_value(env, "CARGO_BUILD_TARGET");
+    let target = target.as_deref();
+    let mut command = Command::new(cargo);
+    command.arg("build");
+    if profile == "release" {
+        command.arg("--release");
+    }
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    if let Some(features) = env_value(env, FEATURES_ENV_VAR).filter(|f| !f.is_empty()) {
+        command.args(["--features", features]);
+    }
+    if env_value(env, NO_DEFAULT_FEATURES_ENV_VAR) == Some("1") {
+        command.arg("--no-default-features");
+    }
+    command
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .current_dir(source_dir);
+    verbose!("Running: {:?} (in {})", command, source_dir.display());
+
+    let status = run_logged(&mut command, "cargo", log_path)?;
+    if !status.success() {
+        let exit_code = status.code().unwrap_or(-1);
+        let hint = target.map_or_else(String::new, |target| {
+            format!(
+                "\nIf cargo reports that the target may not be installed, run 'rustup target add {}'",
+                target
+            )
+        });
+        return Err(format!("Build failed (exit code: {}){}", exit_code, hint).into());
+    }
+
+    status!("Build complete!");
+    let target_dir = cargo_env_value(env, "CARGO_TARGET_DIR")
+        .map_or_else(|| source_dir.join("target"), |dir| source_dir.join(dir));
+    // Cross-compiled binaries go into a subdirectory named after the target
+    let target_dir = match target {
+        Some(target) => target_dir.join(target),
+        None => target_dir,
+    };
+    Ok(target_dir.join(profile))
+}
+
+/// Look up a variable in a list of environment variables
+///
+/// The last value wins, as it does for a child's environment.
+fn env_value<'e>(env: &'e [(String, String)], key: &str) -> Option<&'e str> {
+    env.iter()
+        .rev()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Look up a variable as cargo will see it: from `env`, or else inherited from
+/// Zircon's own environment
+///
+/// Empty values are treated as unset.
+fn cargo_env_value(env: &[(String, String)], key: &str) -> Option<String> {
+    env_value(env, key)
+        .map(str::to_string)
+        .or_else(|| std::env::var(key).ok())
+        .filter(|value| !value.is_empty())
+}
+
+/// Check if cargo is available, reporting where it was found
+pub fn check_cargo(cargo: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let result = Command::new(cargo).arg("--version").output();
 
     match result {
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            Err("cargo not found. Please install Rust from https://rustup.rs/".into())
-        }
-        Err(e) => Err(format!("Failed to execute cargo: {}", e).into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "{} not found. Please install Rust from https://rustup.rs/, or pass --cargo or set CARGO to its path",
+            cargo.display()
+        )
+        .into()),
+        Err(e) => Err(format!("Failed to execute {}: {}", cargo.display(), e).into()),
         Ok(output) => {
             if output.status.success() {
                 let version = String::from_utf8_lossy(&output.stdout);
-                println!("Found cargo: {}", version.trim());
+                let location = deps::find_in_path(&cargo.to_string_lossy())
+                    .unwrap_or_else(|| cargo.to_path_buf());
+                status!("Found cargo: {} ({})", version.trim(), location.display());
                 Ok(())
             } else {
                 let exit_code = output.status.code().unwrap_or(-1);
@@ -47,3 +413,86 @@ pub fn check_cargo() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `chunks` through an [`OutputSplitter`], each as a separate read,
+    /// and return what was printed and what was logged
+    fn split(chunks: &[&'static [u8]], show_output: bool) -> io::Result<(String, String)> {
+        let log_file = tempfile::NamedTempFile::new()?;
+        let mut console = Vec::new();
+        let pipe = chunks
+            .iter()
+            .fold(Box::new(io::empty()) as Box<dyn Read>, |pipe, &chunk| {
+                Box::new(pipe.chain(chunk))
+            });
+        OutputSplitter::new(
+            show_output.then_some(&mut console),
+            "[cargo] ".to_string(),
+            Arc::new(Mutex::new(log_file.reopen()?)),
+        )
+        .copy_from(pipe)?;
+
+        Ok((
+            String::from_utf8_lossy(&console).into_owned(),
+            std::fs::read_to_string(log_file.path())?,
+        ))
+    }
+
+    /// Every console line is prefixed, and the log gets the lines as they are
+    #[test]
+    fn prefixes_console_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let (console, log) = split(&[b"Compiling a\nCompi", b"ling b\nunfinished"], true)?;
+        assert_eq!(
+            console,
+            "[cargo] Compiling a\n[cargo] Compiling b\n[cargo] unfinished"
+        );
+        assert_eq!(log, "Compiling a\nCompiling b\nunfinished\n");
+        Ok(())
+    }
+
+    /// A `\r\n` split across two reads ends one line, without an extra
+    /// prefixed empty line
+    #[test]
+    fn joins_crlf_across_reads() -> Result<(), Box<dyn std::error::Error>> {
+        let split_crlf = split(&[b"one\r", b"\ntwo\r\n"], true)?;
+        let whole_crlf = split(&[b"one\r\ntwo\r\n"], true)?;
+        assert_eq!(split_crlf.0, "[cargo] one\r\n[cargo] two\r\n");
+        assert_eq!(split_crlf, whole_crlf);
+        assert_eq!(split_crlf.1, "one\ntwo\n");
+        Ok(())
+    }
+
+    /// A lone `\r` starts a new prefixed line, and the redrawn line is left
+    /// out of the log
+    #[test]
+    fn redrawn_lines_are_not_logged() -> Result<(), Box<dyn std::error::Error>> {
+        let (console, log) = split(&[b"Building 1/2\r", b"Building 2/2\r", b"Finished\n"], true)?;
+        assert_eq!(
+            console,
+            "[cargo] Building 1/2\r[cargo] Building 2/2\r[cargo] Finished\n"
+        );
+        assert_eq!(log, "Finished\n");
+        Ok(())
+    }
+
+    /// Color codes are printed but left out of the log
+    #[test]
+    fn strips_colors_from_log() -> Result<(), Box<dyn std::error::Error>> {
+        let (console, log) = split(&[b"\x1b[1m\x1b[32mCompil", b"ing\x1b[0m a\n"], true)?;
+        assert_eq!(console, "[cargo] \x1b[1m\x1b[32mCompiling\x1b[0m a\n");
+        assert_eq!(log, "Compiling a\n");
+        Ok(())
+    }
+
+    /// With `--quiet` nothing is printed, but the output is still logged
+    #[test]
+    fn quiet_only_logs() -> Result<(), Box<dyn std::error::Error>> {
+        let (console, log) = split(&[b"warning: unused\n"], false)?;
+        assert_eq!(console, "");
+        assert_eq!(log, "warning: unused\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! SHA256 checksum helpers
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// Compute the full SHA256 hex digest of a file
+pub fn sha256_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Get the path of the `.sha256` sidecar file for a file
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    PathBuf::from(sidecar)
+}
+
+/// Write a `.sha256` sidecar file next to a file, in `sha256sum` format
+///
+/// Returns the file's digest.
+pub fn write_sidecar(path: &Path) -> Result<String, Box<dyn Error>> {
+    let digest = sha256_file(path)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    fs::write(sidecar_path(path), format!("{}  {}\n", digest, file_name))?;
+    Ok(digest)
+}
+
+/// Parse the digest of `file_name` from the contents of a `.sha256` sidecar
+/// file
+///
+/// Accepts either a bare hex digest or the `sha256sum` format (`<hash>
+/// <filename>`, with a `*` before the filename in binary mode). Lines for
+/// other files are skipped, so a sidecar naming a different file yields
+/// `None`.
+pub fn parse_sidecar(contents: &str, file_name: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.trim();
+        let (hash, name) = match line.split_once(char::is_whitespace) {
+            Some((hash, name)) => {
+                let name = name.trim_start();
+                (hash, Some(name.strip_prefix('*').unwrap_or(name)))
+            }
+            None => (line, None),
+        };
+
+        let is_digest = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+        (is_digest && name.is_none_or(|name| name == file_name)).then(|| hash.to_lowercase())
+    })
+}
+
+/// Compare two hex digests, ignoring case
+pub fn digests_match(expected: &str, actual: &str) -> bool {
+    expected.trim().eq_ignore_ascii_case(actual.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The digest of an empty file
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    /// A bare digest applies to whatever file the sidecar belongs to
+    #[test]
+    fn parses_bare_digest() {
+        assert_eq!(
+            parse_sidecar(&format!("{}\n", EMPTY_SHA256), "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+    }
+
+    /// `sha256sum` output in text (`<hash>  <file>`) and binary
+    /// (`<hash> *<file>`) mode
+    #[test]
+    fn parses_sha256sum_format() {
+        let text_mode = format!("{}  zrc.tar.gz\n", EMPTY_SHA256);
+        assert_eq!(
+            parse_sidecar(&text_mode, "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+
+        let binary_mode = format!("{} *zrc.tar.gz\n", EMPTY_SHA256);
+        assert_eq!(
+            parse_sidecar(&binary_mode, "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+    }
+
+    /// A sidecar for another file has no digest for this one, but a list of
+    /// several files does
+    #[test]
+    fn rejects_mismatched_filename() {
+        let other = format!("{}  zrc-other.tar.gz\n", EMPTY_SHA256);
+        assert_eq!(parse_sidecar(&other, "zrc.tar.gz"), None);
+
+        let list = format!(
+            "{}  a.tar.gz\n{}  zrc.tar.gz\n",
+            "0".repeat(64),
+            EMPTY_SHA256
+        );
+        assert_eq!(
+            parse_sidecar(&list, "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+    }
+
+    /// Uppercase digests are normalized, and compare equal to lowercase ones
+    #[test]
+    fn accepts_uppercase_hex() {
+        let upper = EMPTY_SHA256.to_uppercase();
+        assert_eq!(
+            parse_sidecar(&format!("{}  zrc.tar.gz", upper), "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+        assert!(digests_match(&upper, EMPTY_SHA256));
+    }
+
+    /// Anything that isn't a 64-digit hex digest is rejected
+    #[test]
+    fn rejects_malformed_digests() {
+        assert_eq!(parse_sidecar("", "zrc.tar.gz"), None);
+        assert_eq!(parse_sidecar("not a digest", "zrc.tar.gz"), None);
+        assert_eq!(parse_sidecar(&EMPTY_SHA256[..63], "zrc.tar.gz"), None);
+        assert!(!digests_match(EMPTY_SHA256, &"0".repeat(64)));
+    }
+
+    /// A file's digest matches the sidecar written for it
+    #[test]
+    fn sidecar_round_trip() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("zrc.tar.gz");
+        fs::write(&file, "")?;
+
+        assert_eq!(write_sidecar(&file)?, EMPTY_SHA256);
+        let contents = fs::read_to_string(sidecar_path(&file))?;
+        assert_eq!(
+            parse_sidecar(&contents, "zrc.tar.gz").as_deref(),
+            Some(EMPTY_SHA256)
+        );
+        Ok(())
+    }
+}
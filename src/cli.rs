@@ -5,10 +5,16 @@ use std::error::Error;
 use clap::{ArgAction, Parser, Subcommand};
 
 use crate::cmds::build_cmds;
+use crate::cmds::clean_cmds;
+use crate::cmds::completion_cmds;
+use crate::cmds::doctor_cmds;
 use crate::cmds::env_cmds;
 use crate::cmds::install_cmds;
 use crate::cmds::internal_cmds;
+use crate::cmds::override_cmds;
+use crate::cmds::repair_cmds;
 use crate::cmds::self_cmds;
+use crate::cmds::shim_cmds;
 use crate::cmds::toolchain_cmds;
 
 /// The Zircon toolchain installer and build tool
@@ -19,6 +25,18 @@ pub struct Cli {
     #[arg(short, long, action = ArgAction::Version)]
     pub version: (),
 
+    /// Print commands being run and resolved paths
+    #[arg(long, global = true)]
+    pub verbose: bool,
+
+    /// Only print results, warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Don't take the lock that prevents concurrent Zircon operations
+    #[arg(long, global = true)]
+    pub no_lock: bool,
+
     /// The command to run
     #[command(subcommand)]
     pub command: ZirconCommand,
@@ -43,23 +61,81 @@ pub enum ZirconCommand {
     /// Switch to a different toolchain version
     Switch(toolchain_cmds::SwitchCmd),
 
+    /// Run zrc from a specific toolchain without switching
+    Run(toolchain_cmds::RunCmd),
+
     /// List installed toolchains
     List(toolchain_cmds::ListCmd),
 
+    /// Show details about an installed toolchain
+    Info(toolchain_cmds::InfoCmd),
+
+    /// Package an installed toolchain into an archive
+    Export(toolchain_cmds::ExportCmd),
+
+    /// Check installed toolchains for missing or damaged files
+    Verify(toolchain_cmds::VerifyCmd),
+
     /// Delete a specific toolchain
     Delete(toolchain_cmds::DeleteCmd),
 
+    /// Rename an installed toolchain
+    Rename(toolchain_cmds::RenameCmd),
+
     /// Remove unused toolchains (keep only current)
     Prune(toolchain_cmds::PruneCmd),
 
     /// Output shell environment configuration
     Env(env_cmds::EnvCmd),
 
+    /// Manage directory-scoped toolchain overrides
+    #[command(subcommand)]
+    Override(override_cmds::OverrideCmds),
+
+    /// Install zrc and zircop shims that run the active toolchain
+    Shims(shim_cmds::ShimsCmd),
+
+    /// Check the environment for common problems
+    Doctor(doctor_cmds::DoctorCmd),
+
+    /// Recreate missing directories and fix broken links
+    Repair(repair_cmds::RepairCmd),
+
+    /// Print the build log of a toolchain
+    Logs(build_cmds::LogsCmd),
+
+    /// Remove cached build artifacts
+    Clean(clean_cmds::CleanCmd),
+
+    /// Generate shell completion scripts
+    Completions(completion_cmds::CompletionsCmd),
+
     /// Internal commands (for bootstrap and tooling)
     #[command(name = "_", subcommand, hide = true)]
     Internal(internal_cmds::InternalCmds),
 }
 
+impl ZirconCommand {
+    /// Whether the command modifies toolchains, sources or Zircon itself and
+    /// must not run concurrently with another Zircon process
+    pub const fn needs_lock(&self) -> bool {
+        matches!(
+            self,
+            Self::Build(_)
+                | Self::Install(_)
+                | Self::Import(_)
+                | Self::Switch(_)
+                | Self::Delete(_)
+                | Self::Rename(_)
+                | Self::Shims(_)
+                | Self::Prune(_)
+                | Self::Clean(_)
+                | Self::Repair(_)
+        ) || matches!(self, Self::Override(cmds) if cmds.modifies())
+            || matches!(self, Self::SelfCmds(cmds) if cmds.modifies())
+    }
+}
+
 /// A trait for dispatching commands
 pub trait DispatchCommand {
     /// Dispatch the command
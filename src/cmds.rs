@@ -1,8 +1,14 @@
 //! Commands for the Zircon CLI
 
 pub mod build_cmds;
+pub mod clean_cmds;
+pub mod completion_cmds;
+pub mod doctor_cmds;
 pub mod env_cmds;
 pub mod install_cmds;
 pub mod internal_cmds;
+pub mod override_cmds;
+pub mod repair_cmds;
 pub mod self_cmds;
+pub mod shim_cmds;
 pub mod toolchain_cmds;
@@ -1,16 +1,42 @@
 //! Commands for building zrc toolchains
 
-use std::{error::Error, process::Command};
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use clap::Parser;
 
-use crate::{cli::DispatchCommand, deps, git_utils, paths};
+use crate::manifest::{self, Manifest, ToolchainSource};
+use crate::output::{progress, status, success, verbose, warning};
+use crate::{
+    build, cli::DispatchCommand, config::Config, deps, git_utils, installer, paths, platform,
+    toolchains,
+};
 
 /// Build a specific version of zrc
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools)] // independent command-line flags
 pub struct BuildCmd {
     /// The git reference to build (branch, tag, or commit)
-    pub reference: String,
+    #[arg(required_unless_present_any = ["list_refs", "path"])]
+    pub reference: Option<String>,
+
+    /// Build a local zrc directory as it is, without any git operations
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["reference", "repo_url", "shallow", "list_refs", "force", "clean"]
+    )]
+    pub path: Option<PathBuf>,
+
+    /// Name of the toolchain built with `--path` (defaults to
+    /// `local-<timestamp>`)
+    #[arg(long, requires = "path", conflicts_with_all = ["reference", "list_refs"])]
+    pub name: Option<String>,
 
     /// Custom zrc repository URL
     #[arg(
@@ -18,84 +44,703 @@ pub struct BuildCmd {
         default_value = "https://github.com/zirco-lang/zrc.git"
     )]
     pub repo_url: String,
+
+    /// Only fetch the tip of the requested branch or tag when cloning
+    #[arg(long)]
+    pub shallow: bool,
+
+    /// Number of parallel cargo jobs (defaults to `jobs` in the config file,
+    /// then cargo's own default)
+    #[arg(short, long)]
+    pub jobs: Option<u32>,
+
+    /// Build from the existing checkout without touching the network
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Produce a debug build instead of a release build
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Cross-compile for this target triple (e.g.
+    /// `aarch64-unknown-linux-gnu`). Its standard library must be installed
+    #[arg(long, value_name = "TRIPLE", value_parser = parse_target_triple)]
+    pub target: Option<String>,
+
+    /// Cargo features of zrc to enable (comma-separated or repeated)
+    #[arg(long, value_delimiter = ',')]
+    pub features: Vec<String>,
+
+    /// Don't enable zrc's default cargo features
+    #[arg(long)]
+    pub no_default_features: bool,
+
+    /// Acceptable LLVM major versions (comma-separated; defaults to
+    /// `llvm_versions` in the config file)
+    #[arg(long = "llvm-version", value_delimiter = ',')]
+    pub llvm_versions: Vec<String>,
+
+    /// List the branches and tags of the zrc repository instead of building
+    #[arg(long, conflicts_with = "reference")]
+    pub list_refs: bool,
+
+    /// Build with cargo and install the binaries directly, instead of running
+    /// the repository's `hooks/zircon.sh`
+    #[arg(long)]
+    pub no_hook: bool,
+
+    /// Discard local changes in the zrc checkout instead of refusing to build
+    #[arg(long)]
+    pub force: bool,
+
+    /// Remove untracked and ignored files from the zrc checkout before
+    /// building (cargo's `target` directory is kept)
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Print what would be built without building it. The repository is
+    /// still cloned and fetched to resolve the reference, unless `--offline`
+    /// is given
+    #[arg(long, conflicts_with = "list_refs")]
+    pub dry_run: bool,
+
+    /// Cargo to build with (defaults to `CARGO`, then `cargo` from `PATH`).
+    /// Passed to the hook as `CARGO`
+    #[arg(long, value_name = "PATH")]
+    pub cargo: Option<PathBuf>,
+
+    /// Extra environment variable for the build hook (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_pair)]
+    pub env: Vec<(String, String)>,
+}
+
+/// Parse a `KEY=VALUE` pair given to `--env`
+fn parse_env_pair(pair: &str) -> Result<(String, String), String> {
+    let (key, value) = pair
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", pair))?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(format!("invalid environment variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// The source tree a build runs in
+struct BuildSource {
+    /// Directory to build in
+    dir: PathBuf,
+    /// URL the checkout was cloned from; `None` for `--path`
+    origin: Option<String>,
+    /// Name of the toolchain, without the `-debug` suffix of debug builds
+    version: String,
+    /// What is being built: the git reference, or the directory for `--path`
+    reference: String,
+    /// Full SHA of the commit being built, if known
+    commit: Option<String>,
+}
+
+impl BuildSource {
+    /// Short SHA of the commit being built, or an empty string if unknown
+    fn commit_short(&self) -> String {
+        self.commit
+            .as_deref()
+            .map(|commit| commit.chars().take(8).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Check that a `--target` value looks like a target triple
+/// (`<arch>-<vendor>-<os>[-<env>]`, or the two-part `<arch>-<os>` form
+/// some targets use)
+fn parse_target_triple(triple: &str) -> Result<String, String> {
+    let parts: Vec<&str> = triple.split('-').collect();
+    let plausible = (2..=4).contains(&parts.len())
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        });
+    if plausible {
+        Ok(triple.to_string())
+    } else {
+        Err(format!(
+            "'{}' is not a target triple (e.g. aarch64-unknown-linux-gnu); see 'rustc --print target-list'",
+            triple
+        ))
+    }
 }
 
 impl DispatchCommand for BuildCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        if self.list_refs {
+            return list_refs(&self.repo_url);
+        }
+
+        let config = Config::load()?;
+
         // Check dependencies first
-        deps::check_dependencies_strict()?;
+        let llvm_versions = if self.llvm_versions.is_empty() {
+            config.llvm_versions.clone()
+        } else {
+            self.llvm_versions.clone()
+        };
+        let (llvm, clang) = deps::check_dependencies_strict(&llvm_versions)?;
+        build::check_cargo(&build::cargo_program(self.cargo.as_deref()))?;
 
         // Ensure directories exist
         paths::ensure_directories()?;
 
+        let source = match (&self.path, &self.reference) {
+            (Some(path), _) => local_source(path, self.name.as_deref())?,
+            (None, Some(reference)) => self.checkout_source(reference)?,
+            (None, None) => return Err("No reference to build".into()),
+        };
+        let source_dir = &source.dir;
+
+        // Keep cross-compiled, debug and release builds of the same
+        // reference apart; a name given with --name is used as is
+        let mut version = source.version.clone();
+        if self.name.is_none() {
+            if let Some(target) = &self.target {
+                version = format!("{}-{}", version, target);
+            }
+            if self.debug {
+                version = format!("{}-debug", version);
+            }
+        }
+
+        status!("Building version: {}", version);
+
+        // Extra environment for the hook (and the cargo build it runs)
+        let mut hook_env = Vec::new();
+        if let Some(jobs) = self.jobs.or(config.jobs) {
+            hook_env.push(("CARGO_BUILD_JOBS".to_string(), jobs.to_string()));
+        }
+        if self.offline {
+            hook_env.push(("CARGO_NET_OFFLINE".to_string(), "true".to_string()));
+        }
+        let profile = if self.debug { "debug" } else { "release" };
+        hook_env.push(("ZIRCON_BUILD_PROFILE".to_string(), profile.to_string()));
+        // Point llvm-sys at the LLVM that was found, instead of whatever
+        // llvm-config is first on PATH
+        hook_env.extend(llvm.build_env());
+        // Likewise for the C compiler, so a versioned clang (e.g. from
+        // Homebrew) is used rather than the system one
+        hook_env.extend(clang.build_env());
+        // Share compiled dependencies between builds of different references.
+        // A local directory keeps its own target directory, which its own
+        // cargo builds already use.
+        if self.path.is_none() {
+            hook_env.push((
+                "CARGO_TARGET_DIR".to_string(),
+                paths::cargo_target_cache_dir().display().to_string(),
+            ));
+        }
+
+        hook_env.extend(self.cargo_env());
+        hook_env.extend(build_context_env(
+            &version,
+            &source.reference,
+            source.commit_short(),
+        ));
+        // Given last so they can override the variables above
+        hook_env.extend(self.env.iter().cloned());
+
+        let toolchain_dir = paths::toolchain_dir(&version);
+        if self.dry_run {
+            self.print_plan(&source, &version, profile, &hook_env);
+            return Ok(());
+        }
+
+        build_into_toolchain_dir(&version, &toolchain_dir, || {
+            if self.no_hook {
+                build_without_hook(source_dir, &toolchain_dir, profile, &hook_env)?;
+            } else {
+                // Execute the hook script from the zrc repo
+                // The hook handles building and installing to the toolchain directory
+                run_build_hook(source_dir, &toolchain_dir, profile, &hook_env)?;
+            }
+
+            // Record where the toolchain came from. A cross-compiled
+            // toolchain is recorded with the platform it was built for.
+            let mut manifest = Manifest::new(
+                ToolchainSource::Build,
+                Some(source.reference.clone()),
+                source.commit.clone(),
+            );
+            manifest.record_binary_platform(&toolchain_dir);
+            manifest.features.clone_from(&self.features);
+            manifest.no_default_features = self.no_default_features;
+            manifest.record_checksums(&toolchain_dir)?;
+            manifest.write(&toolchain_dir)
+        })?;
+
+        // The log of an earlier failed build is out of date now
+        fs::remove_file(failed_build_log(&version)).ok();
+
+        // Update current symlink
+        let current_link = paths::current_toolchain_link();
+        paths::create_link(&toolchain_dir, &current_link)?;
+
+        success!("\n✓ Successfully built and installed zrc {}", version);
+        status!("  Toolchain location: {}", toolchain_dir.display());
+        status!("\nTo use zrc, run:");
+        status!("  source <(zircon env)");
+
+        Ok(())
+    }
+}
+
+impl BuildCmd {
+    /// Environment selecting the cargo binary, target and features to build
+    /// zrc with
+    fn cargo_env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if let Some(cargo) = &self.cargo {
+            env.push(("CARGO".to_string(), cargo.display().to_string()));
+        }
+        // Cargo builds for this target without the hook having to pass
+        // --target itself
+        if let Some(target) = &self.target {
+            env.push(("CARGO_BUILD_TARGET".to_string(), target.clone()));
+        }
+        // Cargo has no environment variables for features, so hooks have to
+        // pass these on themselves
+        if !self.features.is_empty() {
+            env.push((build::FEATURES_ENV_VAR.to_string(), self.features.join(",")));
+        }
+        if self.no_default_features {
+            env.push((
+                build::NO_DEFAULT_FEATURES_ENV_VAR.to_string(),
+                "1".to_string(),
+            ));
+        }
+        env
+    }
+
+    /// Clone or update the shared zrc checkout and check out `reference`
+    fn checkout_source(&self, reference: &str) -> Result<BuildSource, Box<dyn Error>> {
         let source_dir = paths::zrc_source_dir();
 
+        if self.offline && !source_dir.exists() {
+            return Err(format!(
+                "No local zrc checkout found at {}.\nRun 'zircon build {}' without --offline first to clone it.",
+                source_dir.display(),
+                reference
+            )
+            .into());
+        }
+
         // Clone or open repository
-        let repo = git_utils::clone_or_open(&self.repo_url, &source_dir)?;
+        let repo = git_utils::clone_or_open(&self.repo_url, &source_dir, self.shallow)?;
+        // An existing checkout keeps its origin, whatever --zrc-repo says
+        let origin = repo
+            .find_remote("origin")
+            .ok()
+            .and_then(|remote| remote.url().map(str::to_string));
 
         // Fetch latest changes
-        git_utils::fetch(&repo)?;
+        if self.offline {
+            status!("Offline mode: skipping fetch");
+        } else {
+            git_utils::fetch(&repo, self.shallow)?;
+        }
 
-        // Checkout the requested reference
-        git_utils::checkout_ref(&repo, &self.reference)?;
+        if self.dry_run {
+            // Resolve the reference without touching the working tree
+            let commit = git_utils::resolve_ref(&repo, reference)?.0.id().to_string();
+            let version =
+                git_utils::determine_ref_type(&repo, reference).toolchain_name(&commit[..8]);
+            return Ok(BuildSource {
+                dir: source_dir,
+                origin,
+                version,
+                reference: reference.to_string(),
+                commit: Some(commit),
+            });
+        }
+
+        checkout_reference(&repo, reference, self.offline, self.force)?;
+
+        if self.clean {
+            // Keep cargo's cache so dependencies aren't rebuilt every time
+            let removed = git_utils::clean_untracked(&repo, &["target"])?;
+            status!(
+                "Removed {} untracked file(s) from the zrc checkout",
+                removed
+            );
+        }
 
         // Get commit SHA for version naming
         let commit_sha = git_utils::get_current_commit_short(&repo)?;
 
         // Determine reference type and create appropriate version name
-        let ref_type = git_utils::determine_ref_type(&repo, &self.reference);
-        let version = match ref_type {
-            git_utils::RefType::Tag(tag) => tag,
-            git_utils::RefType::Branch(branch) => {
-                format!("{}@{}", branch.replace('/', "-"), commit_sha)
+        let version = git_utils::determine_ref_type(&repo, reference).toolchain_name(&commit_sha);
+
+        Ok(BuildSource {
+            dir: source_dir,
+            origin,
+            version,
+            reference: reference.to_string(),
+            commit: Some(git_utils::get_current_commit(&repo)?),
+        })
+    }
+
+    /// Print what a build would do, for `--dry-run`
+    fn print_plan(
+        &self,
+        source: &BuildSource,
+        version: &str,
+        profile: &str,
+        hook_env: &[(String, String)],
+    ) {
+        let toolchain_dir = paths::toolchain_dir(version);
+
+        println!("Build plan:");
+        if let Some(origin) = &source.origin {
+            println!(
+                "  Source:     {} (checkout at {})",
+                origin,
+                source.dir.display()
+            );
+            println!("  Reference:  {}", source.reference);
+        } else {
+            println!("  Source:     {} (local)", source.dir.display());
+        }
+        println!(
+            "  Commit:     {}",
+            source.commit.as_deref().unwrap_or("unknown")
+        );
+        println!("  Toolchain:  {}", version);
+        println!(
+            "  Location:   {}{}",
+            toolchain_dir.display(),
+            if toolchain_dir.exists() {
+                " (exists; would be rebuilt)"
+            } else {
+                ""
             }
-            git_utils::RefType::Commit(commit) => commit, // No prefix for commits
-        };
+        );
+        println!("  Profile:    {}", profile);
+        if let Some(target) = &self.target {
+            println!("  Target:     {}", target);
+        }
+        if !self.features.is_empty() || self.no_default_features {
+            println!(
+                "  Features:   {}",
+                manifest::describe_features(&self.features, self.no_default_features)
+            );
+        }
+        println!(
+            "  Build with: {}",
+            if self.no_hook {
+                "cargo (--no-hook)"
+            } else {
+                "the checkout's build hook, or cargo if it has none"
+            }
+        );
+        for (key, value) in hook_env {
+            verbose!("  {}={}", key, value);
+        }
 
-        println!("Building version: {}", version);
+        println!("\nDry run: nothing was built.");
+    }
+}
 
-        // Create toolchain directory
-        let toolchain_dir = paths::toolchain_dir(&version);
-        std::fs::create_dir_all(&toolchain_dir)?;
+/// Build a local directory given with `--path` as it is
+///
+/// Nothing is cloned, fetched or checked out. If the directory is a git
+/// repository, its `HEAD` is recorded as the commit, even though uncommitted
+/// changes are built too.
+fn local_source(path: &Path, name: Option<&str>) -> Result<BuildSource, Box<dyn Error>> {
+    let dir = path
+        .canonicalize()
+        .map_err(|e| format!("Cannot build {}: {}", path.display(), e))?;
+    if !dir.is_dir() {
+        return Err(format!("Cannot build {}: not a directory", dir.display()).into());
+    }
 
-        // Execute the hook script from the zrc repo
-        // The hook handles building and installing to the toolchain directory
-        run_build_hook(&source_dir, &toolchain_dir)?;
+    let version = if let Some(name) = name {
+        toolchains::validate_toolchain_name(name)?;
+        name.to_string()
+    } else {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        format!("local-{}", timestamp)
+    };
 
-        // Update current symlink
-        let current_link = paths::current_toolchain_link();
-        paths::create_link(&toolchain_dir, &current_link)?;
+    let commit = git2::Repository::open(&dir)
+        .ok()
+        .and_then(|repo| git_utils::get_current_commit(&repo).ok());
+
+    status!("Building local zrc at {}", dir.display());
+
+    Ok(BuildSource {
+        reference: dir.display().to_string(),
+        dir,
+        origin: None,
+        version,
+        commit,
+    })
+}
+
+/// Run `build` to populate a toolchain directory
+///
+/// If the build fails and the directory didn't exist before, it is removed
+/// again so no half-populated toolchain is left behind.
+fn build_into_toolchain_dir(
+    version: &str,
+    toolchain_dir: &Path,
+    build: impl FnOnce() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let created_toolchain_dir = !toolchain_dir.exists();
+    fs::create_dir_all(toolchain_dir)?;
+    verbose!("Toolchain directory: {}", toolchain_dir.display());
+
+    build().map_err(|e| {
+        match clean_up_failed_build(version, toolchain_dir, created_toolchain_dir) {
+            Some(log) => format!("{}\nSee the build log: {}", e, log.display()).into(),
+            None => e,
+        }
+    })
+}
 
-        println!("\n✓ Successfully built and installed zrc {}", version);
-        println!("  Toolchain location: {}", toolchain_dir.display());
-        println!("\nTo use zrc, run:");
-        println!("  source <(zircon env)");
+/// Remove a toolchain directory created by a failed build
+///
+/// Its build log is kept in the logs directory so `zircon logs` can still show
+/// it. Returns where the build log is, if there is one.
+fn clean_up_failed_build(
+    version: &str,
+    toolchain_dir: &Path,
+    created_toolchain_dir: bool,
+) -> Option<PathBuf> {
+    let log = toolchain_dir.join(build::BUILD_LOG_FILE);
+    let mut log_location = log.exists().then(|| log.clone());
+    if !created_toolchain_dir {
+        return log_location;
+    }
+
+    if log_location.is_some() {
+        let kept_log = failed_build_log(version);
+        if fs::create_dir_all(paths::logs_dir()).is_ok() && fs::rename(&log, &kept_log).is_ok() {
+            log_location = Some(kept_log);
+        } else {
+            log_location = None;
+        }
+    }
+
+    match fs::remove_dir_all(toolchain_dir) {
+        Ok(()) => progress!("Removed partially built toolchain {}", version),
+        Err(remove_err) => warning!(
+            "Warning: Failed to remove partially built toolchain at {}: {}",
+            toolchain_dir.display(),
+            remove_err
+        ),
+    }
+
+    log_location
+}
+
+/// Get where the build log of a failed build of a new toolchain is kept
+fn failed_build_log(version: &str) -> PathBuf {
+    paths::logs_dir().join(format!("{}.log", version))
+}
+
+/// Print the build log of a toolchain
+#[derive(Parser)]
+pub struct LogsCmd {
+    /// The toolchain whose build log to print
+    pub version: String,
+}
+
+impl DispatchCommand for LogsCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let toolchain_log = paths::toolchain_dir(&self.version).join(build::BUILD_LOG_FILE);
+        let log = [toolchain_log, failed_build_log(&self.version)]
+            .into_iter()
+            .find(|log| log.is_file())
+            .ok_or_else(|| {
+                format!(
+                    "No build log found for '{}'.\nOnly toolchains built with 'zircon build' have one.",
+                    self.version
+                )
+            })?;
+
+        let mut stdout = io::stdout().lock();
+        io::copy(&mut fs::File::open(&log)?, &mut stdout)?;
 
         Ok(())
     }
 }
 
+/// Describe what is being built to the hook, so it can embed version
+/// information or pick platform-specific steps
+fn build_context_env(version: &str, reference: &str, commit_sha: String) -> [(String, String); 5] {
+    let (platform, arch) = platform::detect_platform_and_arch().unwrap_or_else(|_| {
+        (
+            std::env::consts::OS.to_string(),
+            std::env::consts::ARCH.to_string(),
+        )
+    });
+
+    [
+        ("ZIRCON_VERSION".to_string(), version.to_string()),
+        ("ZIRCON_REF".to_string(), reference.to_string()),
+        ("ZIRCON_COMMIT".to_string(), commit_sha),
+        ("ZIRCON_PLATFORM".to_string(), platform),
+        ("ZIRCON_ARCH".to_string(), arch),
+    ]
+}
+
+/// Checkout the reference to build, falling back to the full history if it
+/// isn't reachable from a shallow clone (e.g. an older commit SHA)
+///
+/// Refuses to touch a checkout with local changes unless `force` is set, in
+/// which case they are discarded.
+fn checkout_reference(
+    repo: &git2::Repository,
+    reference: &str,
+    offline: bool,
+    force: bool,
+) -> Result<(), Box<dyn Error>> {
+    let modified = git_utils::modified_files(repo)?;
+    if !modified.is_empty() {
+        if !force {
+            return Err(format!(
+                "The zrc checkout at {} has local changes:\n  {}\nCommit or stash them, or pass --force to discard them.",
+                repo.workdir().unwrap_or_else(|| repo.path()).display(),
+                modified.join("\n  ")
+            )
+            .into());
+        }
+        warning!(
+            "⚠ Discarding local changes to {} file(s) in the zrc checkout",
+            modified.len()
+        );
+    }
+
+    if let Err(e) = git_utils::checkout_ref(repo, reference, force) {
+        if offline {
+            return Err(format!(
+                "Reference '{}' is not available in the local checkout ({}).\nRun without --offline to fetch it.",
+                reference, e
+            )
+            .into());
+        }
+        if !repo.is_shallow() {
+            return Err(e);
+        }
+        progress!(
+            "'{}' is not available in the shallow clone; fetching full history",
+            reference
+        );
+        git_utils::unshallow(repo)?;
+        git_utils::checkout_ref(repo, reference, force)?;
+    }
+
+    Ok(())
+}
+
+/// Print the branches and tags of a remote repository without cloning it
+fn list_refs(repo_url: &str) -> Result<(), Box<dyn Error>> {
+    let refs = git_utils::ls_remote(repo_url)?;
+
+    let mut branches = Vec::new();
+    let mut tags = Vec::new();
+    for (name, _) in &refs {
+        if let Some(branch) = name.strip_prefix("refs/heads/") {
+            branches.push(branch);
+        } else if let Some(tag) = name.strip_prefix("refs/tags/")
+            // Annotated tags are also listed peeled to their commit
+            && !tag.ends_with("^{}")
+        {
+            tags.push(tag);
+        }
+    }
+    branches.sort_unstable();
+    tags.sort_by(|a, b| toolchains::compare_toolchain_names(a, b));
+
+    for (title, names) in [("Branches", branches), ("Tags", tags)] {
+        println!("{}:", title);
+        if names.is_empty() {
+            println!("  (none)");
+        }
+        for name in names {
+            println!("  {}", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Build zrc with cargo and install it into the toolchain directory without a
+/// build hook
+fn build_without_hook(
+    source_dir: &Path,
+    toolchain_dir: &Path,
+    profile: &str,
+    build_env: &[(String, String)],
+) -> Result<(), Box<dyn Error>> {
+    let target_dir = build::build_zrc(
+        source_dir,
+        profile,
+        build_env,
+        &toolchain_dir.join(build::BUILD_LOG_FILE),
+    )?;
+
+    status!("Installing to {}...", toolchain_dir.display());
+    installer::install_zrc_binary(&target_dir, toolchain_dir)?;
+    installer::install_zircop_binary(&target_dir, toolchain_dir)?;
+    installer::install_include_files(source_dir, toolchain_dir)?;
+
+    Ok(())
+}
+
+/// Print the hook command line and its extra environment with `--verbose`
+fn log_hook_invocation(command: &str, source_dir: &Path, hook_env: &[(String, String)]) {
+    verbose!("Running: {} (in {})", command, source_dir.display());
+    for (key, value) in hook_env {
+        verbose!("  {}={}", key, value);
+    }
+}
+
 /// Run the build hook script from the zrc repository
+///
+/// Checkouts without a hook are built with cargo instead.
 #[cfg(unix)]
 fn run_build_hook(
-    source_dir: &std::path::Path,
-    toolchain_dir: &std::path::Path,
+    source_dir: &Path,
+    toolchain_dir: &Path,
+    profile: &str,
+    hook_env: &[(String, String)],
 ) -> Result<(), Box<dyn Error>> {
     let hook_script = source_dir.join("hooks").join("zircon.sh");
     if !hook_script.exists() {
-        return Err(format!(
-            "Hook script not found at {}. This version of zrc may not support zircon hooks.",
+        status!(
+            "No build hook found at {}; building with cargo instead",
             hook_script.display()
-        )
-        .into());
+        );
+        return build_without_hook(source_dir, toolchain_dir, profile, hook_env);
     }
 
-    println!("Running zrc build hook...");
-    let status = Command::new("bash")
-        .arg(&hook_script)
-        .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
-        .current_dir(source_dir)
-        .status()?;
+    status!("Running zrc build hook...");
+    log_hook_invocation(
+        &format!("bash {}", hook_script.display()),
+        source_dir,
+        hook_env,
+    );
+    let status = build::run_logged(
+        Command::new("bash")
+            .arg(&hook_script)
+            .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
+            .envs(hook_env.iter().map(|(k, v)| (k, v)))
+            .current_dir(source_dir),
+        "hook",
+        &toolchain_dir.join(build::BUILD_LOG_FILE),
+    )?;
 
     if !status.success() {
         let exit_code = status.code().unwrap_or(-1);
@@ -106,52 +751,141 @@ fn run_build_hook(
 }
 
 /// Run the build hook script from the zrc repository (Windows)
+///
+/// Checkouts without a hook are built with cargo instead.
 #[cfg(windows)]
 fn run_build_hook(
-    source_dir: &std::path::Path,
-    toolchain_dir: &std::path::Path,
+    source_dir: &Path,
+    toolchain_dir: &Path,
+    profile: &str,
+    hook_env: &[(String, String)],
 ) -> Result<(), Box<dyn Error>> {
     // Check for PowerShell script first, then batch file
     let ps_hook = source_dir.join("hooks").join("zircon.ps1");
     let bat_hook = source_dir.join("hooks").join("zircon.bat");
 
     if ps_hook.exists() {
-        println!("Running zrc build hook (PowerShell)...");
+        status!("Running zrc build hook (PowerShell)...");
+        log_hook_invocation(
+            &format!(
+                "powershell -ExecutionPolicy Bypass -File {}",
+                ps_hook.display()
+            ),
+            source_dir,
+            hook_env,
+        );
         // Use Bypass to run local scripts regardless of system execution policy.
         // This is safe because the script is part of the zrc repo the user cloned.
-        let status = Command::new("powershell")
-            .args(["-ExecutionPolicy", "Bypass", "-File"])
-            .arg(&ps_hook)
-            .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
-            .current_dir(source_dir)
-            .status()?;
+        let status = build::run_logged(
+            Command::new("powershell")
+                .args(["-ExecutionPolicy", "Bypass", "-File"])
+                .arg(&ps_hook)
+                .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
+                .envs(hook_env.iter().map(|(k, v)| (k, v)))
+                .current_dir(source_dir),
+            "hook",
+            &toolchain_dir.join(build::BUILD_LOG_FILE),
+        )?;
 
         if !status.success() {
             let exit_code = status.code().unwrap_or(-1);
             return Err(format!("Hook script failed (exit code: {})", exit_code).into());
         }
     } else if bat_hook.exists() {
-        println!("Running zrc build hook (batch)...");
-        let status = Command::new("cmd")
-            .args(["/C"])
-            .arg(&bat_hook)
-            .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
-            .current_dir(source_dir)
-            .status()?;
+        status!("Running zrc build hook (batch)...");
+        log_hook_invocation(
+            &format!("cmd /C {}", bat_hook.display()),
+            source_dir,
+            hook_env,
+        );
+        let status = build::run_logged(
+            Command::new("cmd")
+                .args(["/C"])
+                .arg(&bat_hook)
+                .env("ZIRCON_TOOLCHAIN_DIR", toolchain_dir)
+                .envs(hook_env.iter().map(|(k, v)| (k, v)))
+                .current_dir(source_dir),
+            "hook",
+            &toolchain_dir.join(build::BUILD_LOG_FILE),
+        )?;
 
         if !status.success() {
             let exit_code = status.code().unwrap_or(-1);
             return Err(format!("Hook script failed (exit code: {})", exit_code).into());
         }
     } else {
-        return Err(format!(
-            "No Windows hook script found at {} or {}. \
-             This version of zrc may not support Windows builds via zircon hooks.",
+        status!(
+            "No build hook found at {} or {}; building with cargo instead",
             ps_hook.display(),
             bat_hook.display()
-        )
-        .into());
+        );
+        return build_without_hook(source_dir, toolchain_dir, profile, hook_env);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a zrc checkout whose build hook writes part of a toolchain and
+    /// then fails
+    #[cfg(unix)]
+    fn failing_checkout(dir: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(dir.join("hooks"))?;
+        fs::write(
+            dir.join("hooks").join("zircon.sh"),
+            "mkdir -p \"$ZIRCON_TOOLCHAIN_DIR/bin\"\n\
+             touch \"$ZIRCON_TOOLCHAIN_DIR/bin/zrc\"\n\
+             echo 'compiling...'\n\
+             exit 3\n",
+        )?;
+        Ok(())
+    }
+
+    /// A failing hook leaves no toolchain behind, but its log is kept
+    #[cfg(unix)]
+    #[test]
+    fn failed_hook_removes_new_toolchain() -> Result<(), Box<dyn Error>> {
+        let root = tempfile::tempdir()?;
+        let _root = paths::set_test_root(root.path());
+        let source = tempfile::tempdir()?;
+        failing_checkout(source.path())?;
+
+        let toolchain_dir = paths::toolchain_dir("broken");
+        let result = build_into_toolchain_dir("broken", &toolchain_dir, || {
+            run_build_hook(source.path(), &toolchain_dir, "release", &[])
+        });
+
+        let error = result
+            .err()
+            .ok_or("the build should have failed")?
+            .to_string();
+        assert!(error.contains("exit code: 3"), "{}", error);
+        assert!(!toolchain_dir.exists());
+        let log = fs::read_to_string(failed_build_log("broken"))?;
+        assert!(log.contains("compiling..."), "{}", log);
+        Ok(())
+    }
+
+    /// A failed rebuild of an existing toolchain leaves the directory in place
+    #[cfg(unix)]
+    #[test]
+    fn failed_hook_keeps_existing_toolchain() -> Result<(), Box<dyn Error>> {
+        let root = tempfile::tempdir()?;
+        let _root = paths::set_test_root(root.path());
+        let source = tempfile::tempdir()?;
+        failing_checkout(source.path())?;
+
+        let toolchain_dir = paths::toolchain_dir("existing");
+        fs::create_dir_all(toolchain_dir.join("include"))?;
+        let result = build_into_toolchain_dir("existing", &toolchain_dir, || {
+            run_build_hook(source.path(), &toolchain_dir, "release", &[])
+        });
+
+        assert!(result.is_err());
+        assert!(toolchain_dir.join("include").is_dir());
+        Ok(())
+    }
+}
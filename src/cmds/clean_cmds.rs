@@ -0,0 +1,124 @@
+//! Commands for removing cached sources and build artifacts
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser};
+
+use crate::cmds::toolchain_cmds;
+use crate::output::{status, success};
+use crate::{cli::DispatchCommand, paths, toolchains};
+
+/// Remove cached sources and build artifacts
+///
+/// At least one of `--target`, `--sources`, `--cache` and `--all` must be
+/// given. Installed toolchains are never touched.
+#[derive(Parser)]
+#[command(group(ArgGroup::new("what").required(true).multiple(true)))]
+#[allow(clippy::struct_excessive_bools)] // independent command-line flags
+pub struct CleanCmd {
+    /// Remove the cargo target directory shared by zrc builds
+    #[arg(long, group = "what")]
+    pub target: bool,
+
+    /// Remove the zrc git checkout (it is cloned again by the next build)
+    #[arg(long, group = "what")]
+    pub sources: bool,
+
+    /// Remove downloaded release archives
+    #[arg(long, group = "what")]
+    pub cache: bool,
+
+    /// Remove all of the above
+    #[arg(long, group = "what")]
+    pub all: bool,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+}
+
+impl DispatchCommand for CleanCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let mut candidates = Vec::new();
+        if self.target || self.all {
+            candidates.push(paths::cargo_target_cache_dir());
+        }
+        if self.sources || self.all {
+            candidates.push(paths::zrc_source_dir());
+        }
+        if self.cache || self.all {
+            candidates.push(paths::download_cache_dir());
+        }
+
+        let to_remove: Vec<(PathBuf, u64)> = candidates
+            .into_iter()
+            .filter(|dir| dir.exists())
+            .map(|dir| {
+                let size = toolchains::dir_size(&dir);
+                (dir, size)
+            })
+            .collect();
+
+        if to_remove.is_empty() {
+            status!("Nothing to clean: the selected directories don't exist.");
+            return Ok(());
+        }
+
+        println!("Directories to be removed:");
+        for (dir, size) in &to_remove {
+            println!(
+                "  {} ({})",
+                dir.display(),
+                toolchain_cmds::format_size(*size)
+            );
+        }
+
+        if !self.yes {
+            println!("\nProceed with removal? (y/N): ");
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            let input = input.trim().to_lowercase();
+
+            if input != "y" && input != "yes" {
+                println!("Cancelled.");
+                return Ok(());
+            }
+        }
+
+        let mut freed = 0;
+        for (dir, size) in &to_remove {
+            fs::remove_dir_all(dir)?;
+            freed += size;
+            success!("  ✓ Removed {}", dir.display());
+        }
+
+        success!("\n✓ Freed {}", toolchain_cmds::format_size(freed));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Without a flag saying what to remove, the command is rejected rather
+    /// than doing nothing
+    #[test]
+    fn requires_something_to_clean() -> Result<(), Box<dyn Error>> {
+        let error = CleanCmd::try_parse_from(["clean", "-y"])
+            .err()
+            .ok_or("clean without flags was accepted")?;
+        assert_eq!(
+            error.kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+
+        let cmd = CleanCmd::try_parse_from(["clean", "--target", "--cache"])?;
+        assert!(cmd.target && cmd.cache && !cmd.sources && !cmd.all);
+        Ok(())
+    }
+}
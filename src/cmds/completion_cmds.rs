@@ -0,0 +1,248 @@
+//! Commands for generating shell completions
+
+use std::error::Error;
+
+use clap::{Command, CommandFactory, Parser};
+use clap_complete::Shell;
+
+use crate::cli::{Cli, DispatchCommand};
+
+/// Names of the positional arguments that take an installed toolchain
+const TOOLCHAIN_ARG_NAMES: &[&str] = &["version", "old"];
+
+/// Command that prints the installed toolchain names, one per line
+const LIST_TOOLCHAINS: &str = "zircon _ list-toolchain-names 2>/dev/null";
+
+/// Zsh completion function for installed toolchain names
+const ZSH_TOOLCHAIN_COMPLETION: &str = r#"(( $+functions[_zircon_toolchains] )) ||
+_zircon_toolchains() {
+    local -a toolchains
+    toolchains=(${(f)"$(zircon _ list-toolchain-names 2>/dev/null)"})
+    compadd -a toolchains
+}
+
+"#;
+
+/// A positional argument that completes to installed toolchain names
+struct ToolchainArg {
+    /// The subcommands leading to the argument (e.g. `["override", "set"]`)
+    path: Vec<String>,
+    /// The argument's name
+    name: String,
+    /// How many positional arguments of the subcommand come before it
+    index: usize,
+}
+
+impl ToolchainArg {
+    /// The argument's position among the words after `zircon`, counting from 1
+    const fn word(&self) -> usize {
+        self.path.len() + self.index + 1
+    }
+}
+
+/// Print a shell completion script to stdout
+#[derive(Parser)]
+pub struct CompletionsCmd {
+    /// The shell to generate completions for
+    pub shell: Shell,
+}
+
+impl DispatchCommand for CompletionsCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        print!("{}", completion_script(self.shell)?);
+        Ok(())
+    }
+}
+
+/// Generate the completion script for `shell`, with installed toolchain names
+/// hooked in where the shell supports it
+fn completion_script(shell: Shell) -> Result<String, Box<dyn Error>> {
+    let mut cmd = Cli::command();
+    let bin_name = cmd.get_name().to_string();
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, bin_name, &mut script);
+    let script = String::from_utf8(script)?;
+
+    let args = toolchain_args(&cmd);
+    Ok(add_toolchain_completion(shell, script, &args))
+}
+
+/// Find the arguments that complete to installed toolchain names
+///
+/// These are the positional arguments named in [`TOOLCHAIN_ARG_NAMES`], and
+/// the hooks of every shell are generated from this one list.
+fn toolchain_args(cmd: &Command) -> Vec<ToolchainArg> {
+    /// Add the toolchain arguments of `cmd`, reached through `path`, and of its
+    /// subcommands
+    fn collect(cmd: &Command, path: &mut Vec<String>, args: &mut Vec<ToolchainArg>) {
+        for (index, arg) in cmd.get_positionals().enumerate() {
+            if TOOLCHAIN_ARG_NAMES.contains(&arg.get_id().as_str()) {
+                args.push(ToolchainArg {
+                    path: path.clone(),
+                    name: arg.get_id().to_string(),
+                    index,
+                });
+            }
+        }
+        for subcommand in cmd.get_subcommands() {
+            path.push(subcommand.get_name().to_string());
+            collect(subcommand, path, args);
+            path.pop();
+        }
+    }
+
+    let mut args = Vec::new();
+    collect(cmd, &mut Vec::new(), &mut args);
+    args
+}
+
+/// Hook installed toolchain names into the completion of the arguments found
+/// by [`toolchain_args`] (e.g. the `version` of `switch` and `override set`)
+///
+/// The names are looked up at completion time through the hidden
+/// `zircon _ list-toolchain-names` command. Shells without a hook keep the
+/// static script.
+fn add_toolchain_completion(shell: Shell, script: String, args: &[ToolchainArg]) -> String {
+    match shell {
+        Shell::Bash => {
+            // Insert the wrapper before the `complete` registration and point it at the wrapper
+            let (body, registration) = script
+                .rfind("\nif [[ \"${BASH_VERSINFO[0]}\"")
+                .map_or((script.as_str(), ""), |idx| script.split_at(idx));
+            format!(
+                "{}{}{}",
+                body,
+                bash_toolchain_completion(args),
+                registration.replace("complete -F _zircon ", "complete -F _zircon_toolchains ")
+            )
+        }
+        Shell::Zsh => {
+            let script = script
+                .lines()
+                .map(|line| {
+                    // Required positionals start with `':`, optional ones with `'::`
+                    let is_toolchain_arg = args.iter().any(|arg| {
+                        line.starts_with(&format!("':{} -- ", arg.name))
+                            || line.starts_with(&format!("'::{} -- ", arg.name))
+                    });
+                    if is_toolchain_arg {
+                        line.replace(":_default'", ":_zircon_toolchains'")
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            // The helper has to be defined before `_zircon` is first run
+            let (body, entry) = script
+                .rfind("if [ \"$funcstack[1]\" = \"_zircon\" ]")
+                .map_or((script.as_str(), ""), |idx| script.split_at(idx));
+            format!("{}{}{}\n", body, ZSH_TOOLCHAIN_COMPLETION, entry)
+        }
+        Shell::Fish => script + &fish_toolchain_completion(args),
+        Shell::Elvish | Shell::PowerShell | _ => script,
+    }
+}
+
+/// Bash completion for installed toolchain names, wrapping the generated
+/// `_zircon`
+///
+/// Each argument is matched by its word position and the words before it,
+/// e.g. `3:override set` for the version of `zircon override set`.
+fn bash_toolchain_completion(args: &[ToolchainArg]) -> String {
+    let patterns: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            // Earlier positional arguments can be any word
+            format!(
+                "\"{}:{}\"{}",
+                arg.word(),
+                arg.path.join(" "),
+                "\" \"*".repeat(arg.index)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"
+_zircon_toolchains() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ ${{cur}} != -* ]]; then
+        case "${{COMP_CWORD}}:${{COMP_WORDS[*]:1:COMP_CWORD-1}}" in
+            {})
+                COMPREPLY=( $(compgen -W "$({})" -- "${{cur}}") )
+                return 0
+                ;;
+        esac
+    fi
+    _zircon "$@"
+}}
+"#,
+        patterns.join("|"),
+        LIST_TOOLCHAINS
+    )
+}
+
+/// Fish completion for installed toolchain names
+fn fish_toolchain_completion(args: &[ToolchainArg]) -> String {
+    let lines: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let mut condition = Vec::new();
+            for (depth, name) in arg.path.iter().enumerate() {
+                if depth == 0 {
+                    condition.push(format!("__fish_zircon_using_subcommand {}", name));
+                } else {
+                    condition.push(format!("__fish_seen_subcommand_from {}", name));
+                }
+            }
+            condition.push(format!("__fish_is_nth_token {}", arg.word()));
+            format!(
+                "complete -c zircon -n \"{}\" -f -a \"({})\"\n",
+                condition.join("; and "),
+                LIST_TOOLCHAINS
+            )
+        })
+        .collect();
+    format!("\n{}", lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every shell completes toolchain names for the same arguments, including
+    /// nested subcommands and optional arguments
+    #[test]
+    fn shells_complete_the_same_arguments() -> Result<(), Box<dyn Error>> {
+        let args = toolchain_args(&Cli::command());
+        let paths: Vec<String> = args.iter().map(|arg| arg.path.join(" ")).collect();
+        for expected in ["switch", "info", "verify", "rename", "override set", "logs"] {
+            assert!(
+                paths.iter().any(|path| path == expected),
+                "{} isn't completed",
+                expected
+            );
+        }
+
+        let bash = completion_script(Shell::Bash)?;
+        let bash_patterns = bash
+            .lines()
+            .find(|line| line.trim_start().starts_with("\"2:"))
+            .ok_or("no bash patterns")?;
+        assert_eq!(bash_patterns.split('|').count(), args.len());
+        assert!(bash_patterns.contains("\"3:override set\""));
+
+        let zsh = completion_script(Shell::Zsh)?;
+        assert_eq!(zsh.matches(":_zircon_toolchains'").count(), args.len());
+
+        let fish = completion_script(Shell::Fish)?;
+        assert_eq!(fish.matches(LIST_TOOLCHAINS).count(), args.len());
+        assert!(fish.contains(
+            "__fish_zircon_using_subcommand override; and __fish_seen_subcommand_from set; and __fish_is_nth_token 3"
+        ));
+        Ok(())
+    }
+}
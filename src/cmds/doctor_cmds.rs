@@ -0,0 +1,272 @@
+//! Commands for diagnosing the Zircon environment
+
+use std::error::Error;
+use std::process::Command;
+
+use clap::Parser;
+use owo_colors::AnsiColors;
+
+use crate::output::{self, Stream};
+use crate::{build, cli::DispatchCommand, config::Config, deps, paths, toolchains};
+
+/// Check the environment for common problems
+#[derive(Parser)]
+pub struct DoctorCmd;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    /// Everything is fine
+    Pass,
+    /// Zircon works, but something is likely to cause trouble
+    Warn,
+    /// Something Zircon needs is missing or broken
+    Fail,
+}
+
+/// The result of a single check
+#[derive(Debug)]
+struct Check {
+    /// What was checked
+    name: &'static str,
+    /// How the check went
+    status: CheckStatus,
+    /// What was found
+    details: String,
+    /// How to fix a warning or failure
+    hint: Option<String>,
+}
+
+impl Check {
+    /// A passing check
+    const fn pass(name: &'static str, details: String) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            details,
+            hint: None,
+        }
+    }
+
+    /// A warning or failing check with a remediation hint
+    const fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        details: String,
+        hint: String,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            details,
+            hint: Some(hint),
+        }
+    }
+}
+
+impl DispatchCommand for DoctorCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let (config, config_check) = check_config();
+        let llvm_versions_desc = deps::llvm_versions_desc(&config.llvm_versions);
+
+        let checks = [
+            config_check,
+            match deps::check_llvm(&config.llvm_versions) {
+                Ok(llvm) => Check::pass("LLVM", llvm.version),
+                Err(e) => Check::problem(
+                    "LLVM",
+                    CheckStatus::Fail,
+                    e.to_string().lines().next().unwrap_or_default().to_string(),
+                    format!(
+                        "Install {} (see the README), or set ZIRCON_LLVM_CONFIG to its llvm-config",
+                        llvm_versions_desc
+                    ),
+                ),
+            },
+            match deps::check_clang(&config.llvm_versions) {
+                Ok(clang) => Check::pass("clang", clang.version),
+                Err(e) => Check::problem(
+                    "clang",
+                    CheckStatus::Fail,
+                    e.to_string(),
+                    "Install clang (usually packaged with LLVM), or set ZIRCON_CLANG".to_string(),
+                ),
+            },
+            match tool_version(&build::cargo_program(None).to_string_lossy()) {
+                Ok(version) => Check::pass("cargo", version),
+                Err(e) => Check::problem(
+                    "cargo",
+                    CheckStatus::Fail,
+                    e,
+                    "Install Rust from https://rustup.rs/, or set CARGO to its cargo".to_string(),
+                ),
+            },
+            match tool_version("git") {
+                Ok(version) => Check::pass("git", version),
+                Err(e) => Check::problem(
+                    "git",
+                    CheckStatus::Fail,
+                    e,
+                    "Install git from your package manager or https://git-scm.com/".to_string(),
+                ),
+            },
+            check_path(),
+            check_current_toolchain()?,
+        ];
+
+        print_checks(&checks);
+
+        let failed = checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count();
+        if failed > 0 {
+            return Err(format!("{} check(s) failed", failed).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Load the config file, falling back to the defaults for the other checks
+/// if it can't be loaded
+fn check_config() -> (Config, Check) {
+    let config_file = paths::config_file();
+    match Config::load() {
+        Ok(config) if config_file.exists() => (
+            config,
+            Check::pass("config", format!("{} is valid", config_file.display())),
+        ),
+        Ok(config) => (
+            config,
+            Check::pass("config", "no config file, using the defaults".to_string()),
+        ),
+        Err(e) => (
+            Config::default(),
+            Check::problem(
+                "config",
+                CheckStatus::Fail,
+                format!("{} could not be loaded", config_file.display()),
+                format!(
+                    "Fix or remove it (the other checks used the default settings)\n{}",
+                    e
+                ),
+            ),
+        ),
+    }
+}
+
+/// Run `<tool> --version` and return the first line of its output
+fn tool_version(tool: &str) -> Result<String, String> {
+    match Command::new(tool).arg("--version").output() {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or("unknown")
+            .to_string()),
+        Ok(output) => Err(format!(
+            "{} --version failed (exit code: {})",
+            tool,
+            output.status.code().unwrap_or(-1)
+        )),
+        Err(e) => Err(format!("{} not found ({})", tool, e)),
+    }
+}
+
+/// Check that Zircon's bin directory is on `PATH`
+fn check_path() -> Check {
+    let bin_dir = paths::bin_dir();
+    let canonical_bin_dir = bin_dir.canonicalize().ok();
+
+    let on_path = std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|entry| {
+            entry == bin_dir
+                || (canonical_bin_dir.is_some() && entry.canonicalize().ok() == canonical_bin_dir)
+        })
+    });
+
+    if on_path {
+        Check::pass("PATH", format!("{} is on PATH", bin_dir.display()))
+    } else {
+        Check::problem(
+            "PATH",
+            CheckStatus::Warn,
+            format!("{} is not on PATH", bin_dir.display()),
+            path_hint(&bin_dir),
+        )
+    }
+}
+
+/// How to add `bin_dir` to `PATH` on this platform
+#[cfg(windows)]
+fn path_hint(bin_dir: &std::path::Path) -> String {
+    format!(
+        "Add `$env:Path = \"{};$env:Path\"` to your PowerShell profile ($PROFILE), or add the directory to Path in the environment variable settings",
+        bin_dir.display()
+    )
+}
+
+/// How to add `bin_dir` to `PATH` on this platform
+#[cfg(not(windows))]
+fn path_hint(bin_dir: &std::path::Path) -> String {
+    format!(
+        "Add `export PATH=\"{}:$PATH\"` to your shell profile",
+        bin_dir.display()
+    )
+}
+
+/// Check that the `current` link points at an installed toolchain
+fn check_current_toolchain() -> Result<Check, Box<dyn Error>> {
+    let check = match toolchains::get_current_toolchain()? {
+        Some(name) => Check::pass("toolchain", format!("current toolchain is {}", name)),
+        None if let Some(name) = toolchains::get_broken_current_toolchain() => Check::problem(
+            "toolchain",
+            CheckStatus::Fail,
+            format!("current points at missing toolchain '{}'", name),
+            "Run 'zircon repair', or 'zircon switch <version>' to select an installed toolchain"
+                .to_string(),
+        ),
+        None => Check::problem(
+            "toolchain",
+            CheckStatus::Warn,
+            "no toolchain selected".to_string(),
+            "Run 'zircon install' or 'zircon build main' to install one".to_string(),
+        ),
+    };
+
+    Ok(check)
+}
+
+/// Print the checks as a table, followed by hints for anything that didn't pass
+fn print_checks(checks: &[Check]) {
+    let name_width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or_default();
+
+    for check in checks {
+        let (label, color) = match check.status {
+            CheckStatus::Pass => ("pass", AnsiColors::Green),
+            CheckStatus::Warn => ("warn", AnsiColors::Yellow),
+            CheckStatus::Fail => ("fail", AnsiColors::Red),
+        };
+        println!(
+            "{:<width$}  {}  {}",
+            check.name,
+            output::paint(label, color, Stream::Stdout),
+            check.details,
+            width = name_width
+        );
+    }
+
+    let hints: Vec<&Check> = checks.iter().filter(|check| check.hint.is_some()).collect();
+    if !hints.is_empty() {
+        println!();
+        for check in hints {
+            if let Some(hint) = &check.hint {
+                println!("{}: {}", check.name, hint);
+            }
+        }
+    }
+}
@@ -1,46 +1,125 @@
 //! Commands for environment configuration
 
-use std::{error::Error, path::Path};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 
 use clap::Parser;
+use serde::Serialize;
 
-use crate::{cli::DispatchCommand, paths};
+use crate::{cli::DispatchCommand, paths, toolchains};
+
+/// Variable pointing at the active toolchain's directory, e.g. for finding
+/// its `include` directory
+const TOOLCHAIN_DIR_VAR: &str = "ZIRCON_TOOLCHAIN_DIR";
 
 /// Output shell environment configuration
 #[derive(Parser)]
 pub struct EnvCmd {
-    /// Specify shell format (bash, zsh, fish, powershell, cmd)
+    /// Specify shell format (bash, zsh, fish, nu, elvish, powershell, cmd)
     #[arg(long)]
     shell: Option<String>,
+
+    /// Output the environment as JSON instead of a shell script
+    #[arg(long, conflicts_with = "shell")]
+    json: bool,
+
+    /// Output a script that undoes the changes made by `zircon env`
+    #[arg(long, conflicts_with = "json")]
+    unset: bool,
+}
+
+/// Environment changes in a machine-readable form (`zircon env --json`)
+#[derive(Serialize)]
+struct EnvJson {
+    /// Directory to prepend to `PATH`
+    path_prepend: String,
+    /// The selected toolchain's bin directory, prepended to `PATH` ahead of
+    /// `path_prepend` when `ZIRCON_TOOLCHAIN` is set
+    toolchain_path_prepend: Option<String>,
+    /// Script from the active toolchain to source, if any
+    source_script: Option<String>,
+    /// Name of the active toolchain, if any
+    toolchain: Option<String>,
+    /// Value of `ZIRCON_TOOLCHAIN_DIR`, if a toolchain is active
+    toolchain_dir: Option<String>,
 }
 
 impl DispatchCommand for EnvCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        let bin_dir = paths::bin_dir();
-
         // Determine shell type
         let shell_type = self
             .shell
             .map_or_else(detect_shell, |shell| shell.to_lowercase());
 
+        if self.unset {
+            print_unset_script(&shell_type);
+            return Ok(());
+        }
+
+        // `ZIRCON_TOOLCHAIN` selects a toolchain for this shell only; its bin
+        // directory goes ahead of ours so its zrc wins over `current`'s
+        let (toolchain_dir, path_dirs) = toolchains::get_env_toolchain()?.map_or_else(
+            || (paths::current_toolchain_link(), vec![paths::bin_dir()]),
+            |version| {
+                let toolchain_dir = paths::toolchain_dir(&version);
+                let path_dirs = vec![paths::bin_dir(), toolchain_dir.join("bin")];
+                (toolchain_dir, path_dirs)
+            },
+        );
+
+        if self.json {
+            return print_json(&toolchain_dir, &path_dirs);
+        }
+
+        // Only export the toolchain directory when there is one to point at
+        if toolchain_dir.is_dir() {
+            print_set_var(&shell_type, TOOLCHAIN_DIR_VAR, &toolchain_dir);
+        }
+
+        // Directories are prepended one at a time, so the last one ends up first
         match shell_type.as_str() {
             "fish" => {
                 // Fish shell syntax - use double quotes and escape internal quotes
-                let bin_escaped = escape_for_fish(&bin_dir);
-                println!("set -gx PATH {} $PATH;", bin_escaped);
+                for dir in &path_dirs {
+                    println!("set -gx PATH {} $PATH;", escape_for_fish(dir));
+                }
                 // Source the toolchain's env.sh if it exists
-                let toolchain_env_sh = paths::current_toolchain_env_sh();
+                let toolchain_env_sh = toolchain_dir.join("env.sh");
                 if toolchain_env_sh.exists() {
                     let env_sh_escaped = escape_for_fish(&toolchain_env_sh);
                     println!("source {};", env_sh_escaped);
                 }
             }
+            "nu" | "nushell" => {
+                // Nushell syntax - double-quote and escape backslashes and double quotes
+                for dir in &path_dirs {
+                    println!(
+                        "$env.PATH = ($env.PATH | prepend {});",
+                        escape_for_nushell(dir)
+                    );
+                }
+                // Source the toolchain's env.nu if it exists
+                let toolchain_env_nu = toolchain_dir.join("env.nu");
+                if toolchain_env_nu.exists() {
+                    let env_nu_escaped = escape_for_nushell(&toolchain_env_nu);
+                    println!("source {};", env_nu_escaped);
+                }
+            }
+            "elvish" => {
+                // Elvish syntax - prepend to the $paths list using a single-quoted string
+                for dir in &path_dirs {
+                    println!("set paths = [{} $@paths]", escape_for_elvish(dir));
+                }
+            }
             "powershell" | "pwsh" => {
                 // PowerShell syntax - double-quote and escape internal double quotes
-                let bin_escaped = escape_for_powershell(&bin_dir);
-                println!("$env:Path = \"{};$env:Path\";", bin_escaped);
+                for dir in &path_dirs {
+                    println!("$env:Path = \"{};$env:Path\";", escape_for_powershell(dir));
+                }
                 // Source the toolchain's env.ps1 if it exists (PowerShell uses . for sourcing)
-                let toolchain_env_ps1 = paths::current_toolchain_env_ps1();
+                let toolchain_env_ps1 = toolchain_dir.join("env.ps1");
                 if toolchain_env_ps1.exists() {
                     let env_ps1_escaped = escape_for_powershell(&toolchain_env_ps1);
                     println!(". \"{}\";", env_ps1_escaped);
@@ -48,32 +127,24 @@ impl DispatchCommand for EnvCmd {
             }
             "cmd" => {
                 // Windows CMD syntax - escape percent signs and carets
-                let bin_escaped = escape_for_cmd(&bin_dir);
-                println!("set PATH={};%PATH%", bin_escaped);
+                for dir in &path_dirs {
+                    println!("set PATH={};%PATH%", escape_for_cmd(dir));
+                }
                 // Source the toolchain's env.bat if it exists (CMD uses call)
-                let toolchain_env_bat = paths::current_toolchain_env_bat();
+                let toolchain_env_bat = toolchain_dir.join("env.bat");
                 if toolchain_env_bat.exists() {
                     let env_bat_escaped = escape_for_cmd(&toolchain_env_bat);
                     println!("call {}", env_bat_escaped);
                 }
             }
-            "zsh" | "bash" | "sh" => {
-                // Bash/Zsh syntax - use single quotes and escape internal single quotes
-                let bin_escaped = escape_for_posix_shell(&bin_dir);
-                println!("export PATH={}:$PATH;", bin_escaped);
-                // Source the toolchain's env.sh if it exists
-                let toolchain_env_sh = paths::current_toolchain_env_sh();
-                if toolchain_env_sh.exists() {
-                    let env_sh_escaped = escape_for_posix_shell(&toolchain_env_sh);
-                    println!("source {};", env_sh_escaped);
-                }
-            }
+            // Bash/Zsh syntax, also the default for unknown shells - use single
+            // quotes and escape internal single quotes
             _ => {
-                // Default for unknown shells - use POSIX syntax
-                let bin_escaped = escape_for_posix_shell(&bin_dir);
-                println!("export PATH={}:$PATH;", bin_escaped);
+                for dir in &path_dirs {
+                    println!("export PATH={}:$PATH;", escape_for_posix_shell(dir));
+                }
                 // Source the toolchain's env.sh if it exists
-                let toolchain_env_sh = paths::current_toolchain_env_sh();
+                let toolchain_env_sh = toolchain_dir.join("env.sh");
                 if toolchain_env_sh.exists() {
                     let env_sh_escaped = escape_for_posix_shell(&toolchain_env_sh);
                     println!("source {};", env_sh_escaped);
@@ -85,14 +156,173 @@ impl DispatchCommand for EnvCmd {
     }
 }
 
+/// Print a command setting an environment variable to a path in a shell's syntax
+fn print_set_var(shell_type: &str, name: &str, value: &Path) {
+    match shell_type {
+        "fish" => println!("set -gx {} {};", name, escape_for_fish(value)),
+        "nu" | "nushell" => println!("$env.{} = {};", name, escape_for_nushell(value)),
+        "elvish" => println!("set-env {} {}", name, escape_for_elvish(value)),
+        "powershell" | "pwsh" => {
+            println!("$env:{} = \"{}\";", name, escape_for_powershell(value));
+        }
+        "cmd" => println!("set {}={}", name, escape_for_cmd(value)),
+        _ => println!("export {}={};", name, escape_for_posix_shell(value)),
+    }
+}
+
+/// Print a script removing Zircon's directories from `PATH` and unsetting
+/// `ZIRCON_TOOLCHAIN_DIR`
+fn print_unset_script(shell_type: &str) {
+    // The selected toolchain's bin directory is removed even if it has been
+    // deleted since `zircon env` added it
+    let mut path_dirs = vec![paths::bin_dir()];
+    if let Ok(version) = std::env::var(toolchains::TOOLCHAIN_ENV_VAR)
+        && toolchains::validate_toolchain_name(&version).is_ok()
+    {
+        path_dirs.push(paths::toolchain_dir(&version).join("bin"));
+    }
+
+    for line in unset_script(shell_type, &path_dirs) {
+        println!("{}", line);
+    }
+}
+
+/// Build the lines of a script removing `path_dirs` from `PATH` and unsetting
+/// `ZIRCON_TOOLCHAIN_DIR`
+fn unset_script(shell_type: &str, path_dirs: &[PathBuf]) -> Vec<String> {
+    let mut lines = Vec::new();
+    match shell_type {
+        "fish" => {
+            // `contains -i` finds exact matches, without glob patterns
+            for dir in path_dirs {
+                lines.push(format!(
+                    "while set -l i (contains -i -- {} $PATH); set -e PATH[$i]; end;",
+                    escape_for_fish(dir)
+                ));
+            }
+            lines.push(format!("set -e {};", TOOLCHAIN_DIR_VAR));
+        }
+        "nu" | "nushell" => {
+            for dir in path_dirs {
+                lines.push(format!(
+                    "$env.PATH = ($env.PATH | where {{|p| $p != {} }});",
+                    escape_for_nushell(dir)
+                ));
+            }
+            lines.push(format!("hide-env -i {};", TOOLCHAIN_DIR_VAR));
+        }
+        "elvish" => {
+            for dir in path_dirs {
+                lines.push(format!(
+                    "set paths = [(each {{|p| if (not-eq $p {}) {{ put $p }} }} $paths)]",
+                    escape_for_elvish(dir)
+                ));
+            }
+            lines.push(format!("unset-env {}", TOOLCHAIN_DIR_VAR));
+        }
+        "powershell" | "pwsh" => {
+            for dir in path_dirs {
+                lines.push(format!(
+                    "$env:Path = (($env:Path -split ';') | Where-Object {{ $_ -ne \"{}\" }}) -join ';';",
+                    escape_for_powershell(dir)
+                ));
+            }
+            lines.push(format!(
+                "Remove-Item Env:{} -ErrorAction SilentlyContinue;",
+                TOOLCHAIN_DIR_VAR
+            ));
+        }
+        "cmd" => {
+            // Substitution removes the entries in the form `zircon env` added them
+            for dir in path_dirs {
+                lines.push(format!("set PATH=%PATH:{};=%", escape_for_cmd(dir)));
+            }
+            lines.push(format!("set {}=", TOOLCHAIN_DIR_VAR));
+        }
+        // POSIX shells - rebuild PATH from the entries that don't match. The
+        // entries are split with parameter expansion rather than IFS, which zsh
+        // doesn't apply to unquoted variables
+        _ => {
+            let patterns = path_dirs
+                .iter()
+                .map(|dir| escape_for_posix_shell(dir))
+                .collect::<Vec<_>>()
+                .join("|");
+            lines.push(format!(
+                "_zircon_rest=\"$PATH:\"; _zircon_path=; while [ -n \"$_zircon_rest\" ]; do _zircon_dir=\"${{_zircon_rest%%:*}}\"; _zircon_rest=\"${{_zircon_rest#*:}}\"; case \"$_zircon_dir\" in {}) ;; *) _zircon_path=\"${{_zircon_path:+$_zircon_path:}}$_zircon_dir\" ;; esac; done; export PATH=\"$_zircon_path\"; unset _zircon_rest _zircon_path _zircon_dir;",
+                patterns
+            ));
+            lines.push(format!("unset {};", TOOLCHAIN_DIR_VAR));
+        }
+    }
+    lines
+}
+
+/// Print the environment configuration as JSON
+fn print_json(toolchain_dir: &Path, path_dirs: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let env_script = toolchain_dir.join(if cfg!(windows) { "env.ps1" } else { "env.sh" });
+
+    let env_json = EnvJson {
+        path_prepend: paths::bin_dir().display().to_string(),
+        toolchain_path_prepend: path_dirs.get(1).map(|dir| dir.display().to_string()),
+        source_script: env_script
+            .exists()
+            .then(|| env_script.display().to_string()),
+        toolchain: toolchains::get_env_toolchain()?
+            .map_or_else(toolchains::get_current_toolchain, |version| {
+                Ok(Some(version))
+            })?,
+        toolchain_dir: toolchain_dir
+            .is_dir()
+            .then(|| toolchain_dir.display().to_string()),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&env_json)?);
+
+    Ok(())
+}
+
 /// Escape a path for POSIX shells (bash, zsh, sh)
 /// Uses single quotes and escapes internal single quotes
 fn escape_for_posix_shell(path: &Path) -> String {
-    let path_str = path.display().to_string();
+    let path_str = if cfg!(windows) {
+        to_msys_path(&path.display().to_string())
+    } else {
+        path.display().to_string()
+    };
     // Replace ' with '\''
     format!("'{}'", path_str.replace('\'', "'\\''"))
 }
 
+/// Translate a Windows path to the form MSYS2 and Git Bash (`/c/...`) or
+/// Cygwin (`/cygdrive/c/...`) shells use
+fn to_msys_path(path: &str) -> String {
+    let path = path
+        .strip_prefix(r"\\?\")
+        .unwrap_or(path)
+        .replace('\\', "/");
+
+    let mut chars = path.chars();
+    let (Some(drive), Some(':')) = (chars.next(), chars.next()) else {
+        return path;
+    };
+    if !drive.is_ascii_alphabetic() {
+        return path;
+    }
+
+    let prefix = if std::env::var_os("MSYSTEM").is_some() {
+        ""
+    } else {
+        "/cygdrive"
+    };
+    format!(
+        "{}/{}{}",
+        prefix,
+        drive.to_ascii_lowercase(),
+        chars.as_str()
+    )
+}
+
 /// Escape a path for Fish shell
 /// Uses double quotes or falls back to proper escaping
 fn escape_for_fish(path: &Path) -> String {
@@ -106,6 +336,21 @@ fn escape_for_fish(path: &Path) -> String {
     format!("\"{}\"", escaped)
 }
 
+/// Escape a path for Nushell
+/// Uses double quotes and escapes internal backslashes and double quotes
+fn escape_for_nushell(path: &Path) -> String {
+    let path_str = path.display().to_string();
+    let escaped = path_str.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+/// Escape a path for Elvish
+/// Uses single quotes and escapes internal single quotes by doubling them
+fn escape_for_elvish(path: &Path) -> String {
+    let path_str = path.display().to_string();
+    format!("'{}'", path_str.replace('\'', "''"))
+}
+
 /// Escape a path for `PowerShell`
 /// Uses double quotes and escapes internal double quotes
 fn escape_for_powershell(path: &Path) -> String {
@@ -130,9 +375,34 @@ fn escape_for_cmd(path: &Path) -> String {
 
 /// Detect the current shell type
 fn detect_shell() -> String {
-    // On Windows, check for PowerShell or CMD
+    // Nushell sets NU_VERSION on every platform
+    if std::env::var("NU_VERSION").is_ok() {
+        return "nu".to_string();
+    }
+
+    // Elvish sets ELVISH_VERSION on every platform
+    if std::env::var("ELVISH_VERSION").is_ok() {
+        return "elvish".to_string();
+    }
+
+    // On Windows, check for Git Bash/MSYS2/Cygwin, PowerShell or CMD
     #[cfg(windows)]
     {
+        // POSIX shells on Windows usually inherit PSModulePath, so check for
+        // them first. MSYS2 and Git Bash set MSYSTEM; Cygwin sets SHELL
+        if std::env::var_os("MSYSTEM").is_some() {
+            return "bash".to_string();
+        }
+        if let Ok(shell) = std::env::var("SHELL") {
+            let shell_name = Path::new(&shell)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("");
+            if matches!(shell_name, "bash" | "zsh" | "sh" | "dash" | "ksh") {
+                return shell_name.to_string();
+            }
+        }
+
         // Check for PowerShell indicators
         if std::env::var("PSModulePath").is_ok() || std::env::var("PSVersionTable").is_ok() {
             return "powershell".to_string();
@@ -162,6 +432,7 @@ fn detect_shell() -> String {
             // Match against known shells
             match shell_name {
                 "fish" => return "fish".to_string(),
+                "elvish" => return "elvish".to_string(),
                 "zsh" => return "zsh".to_string(),
                 "bash" => return "bash".to_string(),
                 "ksh" | "ksh93" => return "ksh".to_string(),
@@ -184,3 +455,105 @@ fn detect_shell() -> String {
         "sh".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directories `zircon env` adds to `PATH`, one with a space in it
+    fn zircon_dirs() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("/opt/zircon/bin"),
+            PathBuf::from("/opt/my zircon/toolchains/v0.1.0/bin"),
+        ]
+    }
+
+    /// Run a POSIX unset script in `shell` and return the resulting `PATH` and
+    /// `ZIRCON_TOOLCHAIN_DIR` (or `<unset>`)
+    #[cfg(unix)]
+    fn run_posix_unset(shell: &str, path: &str) -> Result<String, Box<dyn Error>> {
+        let script = format!(
+            "{}\nprintf '%s\\n' \"$PATH\" \"${{{}-<unset>}}\"",
+            unset_script(shell, &zircon_dirs()).join("\n"),
+            TOOLCHAIN_DIR_VAR
+        );
+        let output = std::process::Command::new(format!("/bin/{}", shell))
+            .arg("-c")
+            .arg(script)
+            .env("PATH", path)
+            .env(TOOLCHAIN_DIR_VAR, "/opt/zircon/toolchains/v0.1.0")
+            .output()?;
+        assert!(output.status.success(), "{:?}", output);
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// The POSIX script drops every copy of Zircon's directories from `PATH`,
+    /// keeps everything else in order, and unsets `ZIRCON_TOOLCHAIN_DIR`
+    #[cfg(unix)]
+    #[test]
+    fn posix_unset_filters_path() -> Result<(), Box<dyn Error>> {
+        let path = "/opt/zircon/bin:/usr/bin:/opt/my zircon/toolchains/v0.1.0/bin:/opt/zircon/bin2:/bin:/opt/zircon/bin";
+        for shell in ["sh", "bash"] {
+            assert_eq!(
+                run_posix_unset(shell, path)?,
+                "/usr/bin:/opt/zircon/bin2:/bin\n<unset>\n",
+                "{}",
+                shell
+            );
+        }
+        Ok(())
+    }
+
+    /// With only Zircon's directories on `PATH`, the POSIX script leaves it
+    /// empty
+    #[cfg(unix)]
+    #[test]
+    fn posix_unset_empties_zircon_only_path() -> Result<(), Box<dyn Error>> {
+        assert_eq!(run_posix_unset("sh", "/opt/zircon/bin")?, "\n<unset>\n");
+        Ok(())
+    }
+
+    /// The fish script removes each directory with `set -e PATH[$i]` until
+    /// `contains` no longer finds it, then erases `ZIRCON_TOOLCHAIN_DIR`
+    #[test]
+    fn fish_unset_filters_path() {
+        assert_eq!(
+            unset_script("fish", &zircon_dirs()),
+            [
+                "while set -l i (contains -i -- \"/opt/zircon/bin\" $PATH); set -e PATH[$i]; end;",
+                "while set -l i (contains -i -- \"/opt/my zircon/toolchains/v0.1.0/bin\" $PATH); set -e PATH[$i]; end;",
+                "set -e ZIRCON_TOOLCHAIN_DIR;",
+            ]
+        );
+    }
+
+    /// `$` in a directory is escaped, so fish doesn't expand it
+    #[test]
+    fn fish_unset_escapes_dirs() {
+        let lines = unset_script("fish", &[PathBuf::from("/home/$USER/zircon/bin")]);
+        assert_eq!(
+            lines[0],
+            "while set -l i (contains -i -- \"/home/\\$USER/zircon/bin\" $PATH); set -e PATH[$i]; end;"
+        );
+    }
+
+    /// Elvish single-quoted strings only need their own quotes doubled;
+    /// spaces, `$` and double quotes are taken literally
+    #[test]
+    fn elvish_escaping() {
+        assert_eq!(
+            escape_for_elvish(Path::new("/opt/zircon/bin")),
+            "'/opt/zircon/bin'"
+        );
+        assert_eq!(
+            escape_for_elvish(Path::new("/home/o'neil/bin")),
+            "'/home/o''neil/bin'"
+        );
+        assert_eq!(
+            escape_for_elvish(Path::new("/my tools/bin")),
+            "'/my tools/bin'"
+        );
+        assert_eq!(escape_for_elvish(Path::new("/$HOME/bin")), "'/$HOME/bin'");
+        assert_eq!(escape_for_elvish(Path::new("/a\"b/''")), "'/a\"b/'''''");
+    }
+}
@@ -2,119 +2,179 @@
 
 use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
 
 use clap::Parser;
+use reqwest::blocking::Client;
 
 use crate::cli::DispatchCommand;
 use crate::cmds::toolchain_cmds;
+use crate::manifest::{Manifest, ToolchainSource};
+use crate::output::{status, warning};
+use crate::{download, platform, releases};
 
 /// Install pre-built toolchains
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools)] // independent command-line flags
 pub struct InstallCmd {
-    /// The release tag to install (e.g., "nightly", "v0.1.0")
+    /// The release tag to install (e.g., "nightly", "v0.1.0"), or "latest"
+    /// for the newest stable release
     #[arg(default_value = "nightly")]
     pub tag: String,
+
+    /// Let "latest" pick prereleases
+    #[arg(long)]
+    pub pre: bool,
+
+    /// Verify the release's minisign signature before installing (needs
+    /// the public key in `ZIRCON_RELEASE_PUBKEY`)
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Proxy URL to download through (overrides `HTTP(S)_PROXY`)
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Download the archive even if it is in the download cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Allow downloading over plain HTTP (e.g. from an internal mirror)
+    #[arg(long)]
+    pub allow_insecure: bool,
+
+    /// Name to give the toolchain instead of deriving one from the archive
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// List the published releases instead of installing one
+    #[arg(long)]
+    pub list_remote: bool,
 }
 
 impl DispatchCommand for InstallCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        install_tag(&self.tag)
+        let options = download::DownloadOptions {
+            verify_signature: self.verify,
+            proxy: self.proxy,
+            no_cache: self.no_cache,
+            allow_insecure: self.allow_insecure,
+        };
+        // One client for the API queries and the download
+        let client = download::client(&options)?;
+
+        if self.list_remote {
+            return list_remote(&client);
+        }
+
+        let tag = if self.tag == "latest" {
+            let tag = releases::resolve_latest_tag(&client, "zrc", self.pre)?;
+            status!("Latest release is {}", tag);
+            tag
+        } else {
+            self.tag
+        };
+
+        let import_options = toolchain_cmds::ImportOptions {
+            name: self.name,
+            ..Default::default()
+        };
+        install_tag(&client, &tag, &options, &import_options)
     }
 }
 
-/// Install a pre-built toolchain from GitHub releases
-fn install_tag(tag: &str) -> Result<(), Box<dyn Error>> {
-    println!("Installing {} release...", tag);
+/// Get the filename of the release asset for this platform
+fn release_asset_name() -> Result<String, Box<dyn Error>> {
+    Ok(format!(
+        "zrc-{}.{}",
+        platform::get_platform_artifact_name()?,
+        platform::archive_extension()
+    ))
+}
 
-    // Detect platform and architecture
-    let (platform, arch) = detect_platform_and_arch()?;
+/// Print the published zrc releases and whether they have a build for this platform
+fn list_remote(client: &Client) -> Result<(), Box<dyn Error>> {
+    let releases = releases::fetch_releases(client, "zrc")?;
 
-    // Construct download URL
-    let filename = format!("zrc-{}-{}.tar.gz", platform, arch);
-    let url = format!(
-        "https://github.com/zirco-lang/zrc/releases/download/{}/{}",
-        tag, filename
-    );
+    if releases.is_empty() {
+        println!("No releases published.");
+        return Ok(());
+    }
+
+    // Without a supported platform, no release has a build for it
+    let asset_name = release_asset_name().ok();
+    let tag_width = releases
+        .iter()
+        .map(|release| release.tag_name.len())
+        .max()
+        .unwrap_or_default();
+
+    println!("Available releases:");
+    for release in &releases {
+        let mut notes = Vec::new();
+        if release.prerelease {
+            notes.push("prerelease");
+        }
+        if !asset_name
+            .as_ref()
+            .is_some_and(|name| release.has_asset(name))
+        {
+            notes.push("no build for this platform");
+        }
+        let notes = if notes.is_empty() {
+            String::new()
+        } else {
+            format!("  ({})", notes.join(", "))
+        };
+        println!(
+            "  {:<tag_width$}  {:<10}{}",
+            release.tag_name,
+            release.published_date().unwrap_or("unknown"),
+            notes,
+            tag_width = tag_width
+        );
+    }
+
+    Ok(())
+}
 
-    println!("Downloading from: {}", url);
+/// Install a pre-built toolchain from the release server
+fn install_tag(
+    client: &Client,
+    tag: &str,
+    options: &download::DownloadOptions,
+    import_options: &toolchain_cmds::ImportOptions,
+) -> Result<(), Box<dyn Error>> {
+    status!("Installing {} release...", tag);
+
+    // Construct download URL
+    let filename = release_asset_name()?;
+    let url = download::release_url("zrc", tag, &filename)?;
 
     // Create temporary directory for download
     let temp_dir = env::temp_dir();
     let temp_file = temp_dir.join(&filename);
 
     // Download the file
-    download_file(&url, &temp_file)?;
-
-    println!("Download complete. Importing toolchain...");
+    download::download_release_archive(
+        client,
+        &url,
+        &download::cache_path(tag, &filename),
+        &temp_file,
+        options,
+    )?;
 
-    // Use the existing import functionality
-    let import_cmd = toolchain_cmds::ImportCmd {
-        archive: temp_file.clone(),
-    };
+    status!("Download complete. Importing toolchain...");
 
-    // Import the toolchain
-    let result = import_cmd.dispatch();
+    // Import the toolchain using the existing import functionality
+    let manifest = Manifest::new(ToolchainSource::Install, Some(tag.to_string()), None);
+    let result = toolchain_cmds::import_archive(&temp_file, manifest, import_options);
 
     // Clean up the temporary file (best effort)
     if temp_file.exists()
         && let Err(e) = std::fs::remove_file(&temp_file)
     {
-        eprintln!("Warning: Failed to clean up temporary file: {}", e);
+        warning!("Warning: Failed to clean up temporary file: {}", e);
     }
 
     result
 }
-
-/// Detect the current platform and architecture
-fn detect_platform_and_arch() -> Result<(String, String), Box<dyn Error>> {
-    let os = env::consts::OS;
-    let arch = env::consts::ARCH;
-
-    let platform = match os {
-        "linux" => "linux",
-        "macos" => "macos",
-        _ => {
-            return Err(format!(
-                "Unsupported platform: {}. Only linux and macos are supported.",
-                os
-            )
-            .into());
-        }
-    };
-
-    let architecture = match arch {
-        "x86_64" => "x64",
-        "aarch64" => "arm64",
-        _ => {
-            return Err(format!(
-                "Unsupported architecture: {}. Only x86_64 (x64) and aarch64 (arm64) are supported.",
-                arch
-            )
-            .into());
-        }
-    };
-
-    Ok((platform.to_string(), architecture.to_string()))
-}
-
-/// Download a file from a URL to a local path
-fn download_file(url: &str, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let response = reqwest::blocking::get(url)?;
-
-    if !response.status().is_success() {
-        return Err(format!(
-            "Failed to download file: HTTP {}. The release may not be available or may not have pre-built binaries for your platform.",
-            response.status()
-        )
-        .into());
-    }
-
-    let mut file = File::create(dest)?;
-    let content = response.bytes()?;
-    file.write_all(&content)?;
-
-    Ok(())
-}
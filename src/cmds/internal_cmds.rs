@@ -2,75 +2,93 @@
 
 use clap::Subcommand;
 use std::error::Error;
+use std::ffi::OsString;
 
 use crate::cli::DispatchCommand;
-use crate::{deps, paths};
+use crate::cmds::shim_cmds;
+use crate::output::{status, success};
+use crate::{config::Config, deps, paths, toolchains};
 
 /// Internal commands (hidden from normal help)
 #[derive(Subcommand)]
 pub enum InternalCmds {
     /// Bootstrap command run by bootstrap.sh
     Bootstrap,
+
+    /// Print installed toolchain names, one per line (used by shell completions)
+    ListToolchainNames,
+
+    /// Run a tool from the active toolchain, as its shim would
+    Shim {
+        /// The tool to run (e.g. zrc)
+        name: String,
+
+        /// Arguments passed to the tool
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<OsString>,
+    },
 }
 
 impl DispatchCommand for InternalCmds {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
         match self {
             Self::Bootstrap => cmd_bootstrap(),
+            Self::ListToolchainNames => cmd_list_toolchain_names(),
+            Self::Shim { name, args } => shim_cmds::run_shim(&name, &args),
         }
     }
 }
 
 /// Bootstrap the Zircon installation
 fn cmd_bootstrap() -> Result<(), Box<dyn Error>> {
-    println!("=== Zircon Bootstrap ===\n");
+    status!("=== Zircon Bootstrap ===\n");
 
-    // Check dependencies - fail if LLVM 20 is missing
-    deps::check_dependencies_strict()?;
+    // Check dependencies - LLVM is only needed once the user builds a toolchain
+    deps::warn_dependencies(&Config::load()?.llvm_versions);
 
     // Ensure directories exist
     paths::ensure_directories()?;
 
-    println!("\n✓ Bootstrap complete!");
-    println!(
+    success!("\n✓ Bootstrap complete!");
+    status!(
         "\nZircon is installed at: {}",
         paths::zircon_root().display()
     );
 
     #[cfg(windows)]
     {
-        println!("\nNext steps:");
-        println!("  1. Add Zircon to your PATH:");
-        println!(
+        status!("\nNext steps:");
+        status!("  1. Add Zircon to your PATH:");
+        status!(
             "     PowerShell: $env:Path = \"{};$env:Path\"",
             paths::bin_dir().display()
         );
-        println!(
+        status!(
             "     CMD:        set PATH={};%PATH%",
             paths::bin_dir().display()
         );
-        println!("\n  2. Then load the environment with:");
-        println!("     PowerShell: iex (zircon env --shell powershell)");
-        println!("     CMD:        zircon env --shell cmd");
-        println!("\n  3. Install a zrc version:");
-        println!("     zircon build main");
-        println!("     zircon build v0.1.0");
+        status!("\n  2. Then load the environment with:");
+        status!("     PowerShell: iex (zircon env --shell powershell)");
+        status!("     CMD:        zircon env --shell cmd");
+        status!("\n  3. Install a zrc version:");
+        status!("     zircon build main");
+        status!("     zircon build v0.1.0");
     }
 
     #[cfg(not(windows))]
     {
-        println!("\nNext steps:");
-        println!("  1. Add Zircon to your PATH:");
-        println!("     export PATH=\"{}:$PATH\"", paths::bin_dir().display());
-        println!("\n  2. Then load the environment with:");
-        println!("     source <(zircon env)");
-        println!("\n  3. Install a zrc version:");
-        println!("     zircon build main");
-        println!("     zircon build v0.1.0");
-        println!(
+        status!("\nNext steps:");
+        status!("  1. Add Zircon to your PATH:");
+        status!("     export PATH=\"{}:$PATH\"", paths::bin_dir().display());
+        status!("\n  2. Then load the environment with:");
+        status!("     source <(zircon env)");
+        status!("\n  3. Install a zrc version:");
+        status!("     zircon build main");
+        status!("     zircon build v0.1.0");
+        status!(
             "\n  To make these settings permanent, add to your shell profile (~/.bashrc, ~/.zshrc, etc.):"
         );
-        println!(
+        status!(
             "     echo 'source <({}/zircon env)' >> ~/.bashrc",
             paths::bin_dir().display()
         );
@@ -78,3 +96,12 @@ fn cmd_bootstrap() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Print the names of installed toolchains, one per line
+fn cmd_list_toolchain_names() -> Result<(), Box<dyn Error>> {
+    for tc in toolchains::list_toolchains()? {
+        println!("{}", tc.name);
+    }
+
+    Ok(())
+}
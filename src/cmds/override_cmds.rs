@@ -0,0 +1,112 @@
+//! Commands to manage directory-scoped toolchain overrides
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::output::success;
+use crate::overrides::Overrides;
+use crate::{cli::DispatchCommand, toolchains};
+
+/// Valid subcommands on `zircon override`
+#[derive(Subcommand)]
+pub enum OverrideCmds {
+    /// Use a toolchain in the current directory and its subdirectories
+    Set(SetOverrideCmd),
+
+    /// Remove the override for the current directory
+    Unset,
+
+    /// List all overrides
+    List,
+}
+
+/// Use a toolchain in the current directory and its subdirectories
+#[derive(Parser)]
+pub struct SetOverrideCmd {
+    /// The version to use
+    pub version: String,
+}
+
+impl OverrideCmds {
+    /// Whether the command changes `overrides.toml`
+    pub const fn modifies(&self) -> bool {
+        matches!(self, Self::Set(_) | Self::Unset)
+    }
+}
+
+impl DispatchCommand for OverrideCmds {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Set(cmd) => cmd_override_set(&cmd.version),
+            Self::Unset => cmd_override_unset(),
+            Self::List => cmd_override_list(),
+        }
+    }
+}
+
+/// Get the key overrides for the current directory are stored under
+fn current_dir_key() -> Result<String, Box<dyn Error>> {
+    let dir: PathBuf = std::env::current_dir()?.canonicalize()?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// Set the override for the current directory
+fn cmd_override_set(version: &str) -> Result<(), Box<dyn Error>> {
+    toolchains::validate_toolchain_name(version)?;
+    if !toolchains::toolchain_exists(version) {
+        return Err(format!(
+            "Toolchain '{}' not found.\nUse 'zircon list' to see installed toolchains.",
+            version
+        )
+        .into());
+    }
+
+    let dir = current_dir_key()?;
+    let mut overrides = Overrides::load()?;
+    overrides.overrides.insert(dir.clone(), version.to_string());
+    overrides.save()?;
+
+    success!("✓ Override set: {} uses {}", dir, version);
+
+    Ok(())
+}
+
+/// Remove the override for the current directory
+fn cmd_override_unset() -> Result<(), Box<dyn Error>> {
+    let dir = current_dir_key()?;
+    let mut overrides = Overrides::load()?;
+
+    if overrides.overrides.remove(&dir).is_none() {
+        return Err(format!("No override set for {}", dir).into());
+    }
+    overrides.save()?;
+
+    success!("✓ Override removed for {}", dir);
+
+    Ok(())
+}
+
+/// List all overrides
+fn cmd_override_list() -> Result<(), Box<dyn Error>> {
+    let overrides = Overrides::load()?;
+
+    if overrides.overrides.is_empty() {
+        println!("No overrides set.");
+        return Ok(());
+    }
+
+    for (dir, version) in &overrides.overrides {
+        let missing = if toolchains::validate_toolchain_name(version).is_err() {
+            " (invalid name)"
+        } else if toolchains::toolchain_exists(version) {
+            ""
+        } else {
+            " (not installed)"
+        };
+        println!("{}  {}{}", dir, version, missing);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Commands for repairing a damaged Zircon installation
+
+use std::error::Error;
+use std::fs;
+
+use clap::Parser;
+
+use crate::output::{success, warning};
+use crate::{cli::DispatchCommand, paths, toolchains};
+
+/// Recreate missing directories and fix broken links
+///
+/// Only things that are missing or broken are changed, so this is safe to run
+/// at any time.
+#[derive(Parser)]
+pub struct RepairCmd;
+
+impl DispatchCommand for RepairCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let mut changes = repair_directories()?;
+        changes.extend(repair_current_link()?);
+        changes.extend(repair_zircon_link()?);
+
+        if changes.is_empty() {
+            success!("✓ Nothing to repair");
+        }
+        for change in changes {
+            success!("✓ {}", change);
+        }
+
+        Ok(())
+    }
+}
+
+/// Create any of Zircon's directories that are missing
+fn repair_directories() -> Result<Vec<String>, Box<dyn Error>> {
+    let missing: Vec<String> = paths::required_directories()
+        .iter()
+        .filter(|dir| !dir.is_dir())
+        .map(|dir| format!("Created {}", dir.display()))
+        .collect();
+
+    paths::ensure_directories()?;
+    Ok(missing)
+}
+
+/// Point a dangling `current` link at an installed toolchain
+///
+/// A toolchain with the same name is preferred (e.g. after the Zircon root
+/// was moved), then the most recently installed one. Without any toolchains,
+/// the link is removed.
+fn repair_current_link() -> Result<Option<String>, Box<dyn Error>> {
+    let Some(missing) = toolchains::get_broken_current_toolchain() else {
+        return Ok(None);
+    };
+    let current_link = paths::current_toolchain_link();
+
+    let installed = toolchains::list_toolchains()?;
+    let replacement = installed
+        .iter()
+        .find(|tc| tc.name == missing)
+        .or_else(|| installed.iter().max_by_key(|tc| tc.installed_timestamp()));
+
+    let change = if let Some(tc) = replacement {
+        paths::create_link(&tc.path, &current_link)?;
+        format!(
+            "Pointed current at {} (it pointed at missing toolchain '{}')",
+            tc.name, missing
+        )
+    } else {
+        // Directory links on Windows have to be removed as directories
+        fs::remove_file(&current_link).or_else(|_| fs::remove_dir(&current_link))?;
+        format!(
+            "Removed current, which pointed at missing toolchain '{}' (no toolchains are installed)",
+            missing
+        )
+    };
+
+    Ok(Some(change))
+}
+
+/// Recreate a missing or dangling `bin/zircon` link
+fn repair_zircon_link() -> Result<Option<String>, Box<dyn Error>> {
+    let zircon_link = paths::zircon_binary_link();
+    if zircon_link.exists() {
+        return Ok(None);
+    }
+
+    // Pre-built releases are unpacked into self/bin; source builds stay in
+    // the Zircon checkout
+    let binary_name = format!("zircon{}", std::env::consts::EXE_SUFFIX);
+    let candidates = [
+        paths::self_dir().join("bin").join(binary_name),
+        paths::self_zircon_binary(),
+    ];
+    let Some(zircon_binary) = candidates.iter().find(|binary| binary.is_file()) else {
+        warning!(
+            "⚠ {} is missing, but no Zircon binary was found to link it to.\nReinstall Zircon with 'zircon self install' or the bootstrap script.",
+            zircon_link.display()
+        );
+        return Ok(None);
+    };
+
+    if zircon_link.symlink_metadata().is_ok() {
+        fs::remove_file(&zircon_link)?;
+    }
+    paths::create_link(zircon_binary, &zircon_link)?;
+
+    Ok(Some(format!(
+        "Linked {} to {}",
+        zircon_link.display(),
+        zircon_binary.display()
+    )))
+}
@@ -6,7 +6,8 @@ use std::{error::Error, fs};
 
 use clap::{Parser, Subcommand};
 
-use crate::cli::DispatchCommand;
+use crate::output::{status, success, verbose, warning};
+use crate::{cli::DispatchCommand, cmds::toolchain_cmds, platform};
 
 /// Valid subcommands on `zircon self`
 #[derive(Subcommand)]
@@ -17,11 +18,21 @@ pub enum SelfCmds {
     /// Build Zircon itself from source
     Build(BuildSelfCmd),
 
+    /// Update Zircon to the latest version of the main branch
+    Update,
+
     /// Import Zircon from an archive
     Import(ImportSelfCmd),
 
     /// Install a pre-built Zircon release
     Install(InstallSelfCmd),
+
+    /// Swap back to the Zircon binary from before the last build, update,
+    /// import or install
+    Rollback,
+
+    /// Remove Zircon and everything it manages
+    Uninstall(UninstallSelfCmd),
 }
 
 /// Build Zircon itself from source
@@ -30,13 +41,21 @@ pub struct BuildSelfCmd {
     /// Git reference to build (branch, tag, or commit). Defaults to 'main'
     #[arg(default_value = "main")]
     pub reference: String,
+
+    /// Cargo to build with (defaults to `CARGO`, then `cargo` from `PATH`)
+    #[arg(long, value_name = "PATH")]
+    pub cargo: Option<std::path::PathBuf>,
 }
 
 /// Import Zircon from an archive file
 #[derive(Parser)]
 pub struct ImportSelfCmd {
-    /// Path to the archive (.tar.gz, .tar, or .zip) containing Zircon
+    /// Path or http(s):// URL of the archive (.tar.gz, .tar.xz, .tar.bz2, .tar.zst, .tar, or .zip) containing Zircon
     pub archive: std::path::PathBuf,
+
+    /// Allow downloading the archive over plain HTTP
+    #[arg(long)]
+    pub allow_insecure: bool,
 }
 
 /// Install a pre-built Zircon release
@@ -45,6 +64,45 @@ pub struct InstallSelfCmd {
     /// The release tag to install (e.g., "nightly", "v0.1.0")
     #[arg(default_value = "nightly")]
     pub tag: String,
+
+    /// Verify the release's minisign signature before installing (needs
+    /// the public key in `ZIRCON_RELEASE_PUBKEY`)
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Proxy URL to download through (overrides `HTTP(S)_PROXY`)
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Download the archive even if it is in the download cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Allow downloading over plain HTTP (e.g. from an internal mirror)
+    #[arg(long)]
+    pub allow_insecure: bool,
+}
+
+/// Remove Zircon and everything it manages
+#[derive(Parser)]
+pub struct UninstallSelfCmd {
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Only remove Zircon itself, keeping installed toolchains
+    #[arg(long)]
+    pub keep_toolchains: bool,
+}
+
+impl SelfCmds {
+    /// Whether the command replaces the Zircon binary
+    ///
+    /// `self uninstall` takes the lock itself, since it has to release it
+    /// before deleting the lock file.
+    pub const fn modifies(&self) -> bool {
+        !matches!(self, Self::Version | Self::Uninstall(_))
+    }
 }
 
 impl DispatchCommand for SelfCmds {
@@ -54,32 +112,131 @@ impl DispatchCommand for SelfCmds {
                 cmd_version::cmd_version();
                 Ok(())
             }
-            Self::Build(cmd) => cmd_self_build(&cmd.reference),
-            Self::Import(cmd) => cmd_self_import(&cmd.archive),
-            Self::Install(cmd) => cmd_self_install(&cmd.tag),
+            Self::Build(cmd) => cmd_self_build(
+                &cmd.reference,
+                &crate::build::cargo_program(cmd.cargo.as_deref()),
+            ),
+            Self::Update => cmd_self_update(&crate::build::cargo_program(None)),
+            Self::Import(cmd) => {
+                let options = crate::download::DownloadOptions {
+                    allow_insecure: cmd.allow_insecure,
+                    ..Default::default()
+                };
+                crate::download::with_local_archive(&cmd.archive, &options, cmd_self_import)
+            }
+            Self::Install(cmd) => {
+                let options = crate::download::DownloadOptions {
+                    verify_signature: cmd.verify,
+                    proxy: cmd.proxy,
+                    no_cache: cmd.no_cache,
+                    allow_insecure: cmd.allow_insecure,
+                };
+                cmd_self_install(&cmd.tag, &options)
+            }
+            Self::Rollback => cmd_self_rollback(),
+            Self::Uninstall(cmd) => cmd_self_uninstall(cmd.yes, cmd.keep_toolchains),
         }
     }
 }
 
 /// Build Zircon itself from source
-fn cmd_self_build(reference: &str) -> Result<(), Box<dyn Error>> {
-    use crate::{build, git_utils, paths};
+fn cmd_self_build(reference: &str, cargo: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    use crate::{deps, git_utils, paths};
+
+    // Fail before cloning anything if Zircon can't be built here
+    deps::check_self_build_dependencies(cargo)?;
 
-    println!("Building Zircon from '{}'...", reference);
+    status!("Building Zircon from '{}'...", reference);
 
     let zircon_source = paths::zircon_source_dir();
 
     // Clone or open the zircon repository
-    let repo =
-        git_utils::clone_or_open("https://github.com/zirco-lang/zircon.git", &zircon_source)?;
+    let repo = git_utils::clone_or_open(
+        "https://github.com/zirco-lang/zircon.git",
+        &zircon_source,
+        false,
+    )?;
 
     // Fetch and checkout the specified reference
-    git_utils::fetch(&repo)?;
-    git_utils::checkout_ref(&repo, reference)?;
+    git_utils::fetch(&repo, false)?;
+    git_utils::checkout_ref(&repo, reference, false)?;
+
+    build_and_install_self(&zircon_source, cargo)?;
+
+    success!("✓ Zircon built successfully from '{}'!", reference);
+
+    Ok(())
+}
+
+/// Update Zircon to the latest `main`, rebuilding only if it has moved
+fn cmd_self_update(cargo: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    use crate::{deps, git_utils, paths};
+
+    let zircon_source = paths::zircon_source_dir();
+
+    if !zircon_source.exists() {
+        return Err(format!(
+            "Zircon sources not found at {}.\nIf Zircon was installed from a pre-built release, use 'zircon self install' to update it.",
+            zircon_source.display()
+        )
+        .into());
+    }
+
+    status!("Checking for Zircon updates...");
+
+    let repo = git2::Repository::open(&zircon_source)?;
+    let local_oid = repo.head()?.peel_to_commit()?.id();
+
+    // Ask the remote where main is before downloading anything
+    let origin = repo.find_remote("origin")?;
+    let url = origin
+        .url()
+        .ok_or("The Zircon checkout's origin has no URL")?;
+    if git_utils::resolve_remote_ref(url, "main")? == local_oid {
+        success!("✓ Zircon is already up to date");
+        return Ok(());
+    }
+
+    deps::check_self_build_dependencies(cargo)?;
+    git_utils::fetch(&repo, false)?;
+    git_utils::checkout_ref(&repo, "main", false)?;
 
-    println!("Building Zircon...");
-    build::check_cargo()?;
-    build::build_rust_project(&zircon_source)?;
+    build_and_install_self(&zircon_source, cargo)?;
+
+    success!("✓ Zircon updated to the latest 'main'!");
+
+    Ok(())
+}
+
+/// Name of the file in the logs directory that the output of the last Zircon
+/// build is saved to
+const SELF_BUILD_LOG_FILE: &str = "self-build.log";
+
+/// Build Zircon from a checked-out source tree and link the new binary
+///
+/// If anything fails, the previous binary is restored.
+fn build_and_install_self(
+    zircon_source: &std::path::Path,
+    cargo: &std::path::Path,
+) -> Result<(), Box<dyn Error>> {
+    with_self_backup(|| build_and_link_self(zircon_source, cargo))
+}
+
+/// Build Zircon from a checked-out source tree and link the new binary,
+/// without a backup
+fn build_and_link_self(
+    zircon_source: &std::path::Path,
+    cargo: &std::path::Path,
+) -> Result<(), Box<dyn Error>> {
+    use crate::{build, paths};
+
+    status!("Building Zircon...");
+    fs::create_dir_all(paths::logs_dir())?;
+    build::build_rust_project(
+        zircon_source,
+        cargo,
+        &paths::logs_dir().join(SELF_BUILD_LOG_FILE),
+    )?;
 
     // Copy the new binary
     let binary_name = if cfg!(windows) {
@@ -100,9 +257,9 @@ fn cmd_self_build(reference: &str) -> Result<(), Box<dyn Error>> {
     // Only copy if source and destination are different paths
     // If they're the same, the binary is already in place from cargo build
     if new_binary == self_binary {
-        println!("Binary already in place from build...");
+        status!("Binary already in place from build...");
     } else {
-        println!("Installing updated binary...");
+        status!("Installing updated binary...");
         fs::copy(&new_binary, &self_binary)?;
 
         // Make executable on Unix
@@ -119,14 +276,12 @@ fn cmd_self_build(reference: &str) -> Result<(), Box<dyn Error>> {
     let zircon_link = paths::zircon_binary_link();
     paths::create_link(&self_binary, &zircon_link)?;
 
-    println!("✓ Zircon built successfully from '{}'!", reference);
-
     Ok(())
 }
 
 /// Import Zircon from an archive
 fn cmd_self_import(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
-    println!("Importing Zircon from archive...");
+    status!("Importing Zircon from archive...");
 
     // Verify archive exists
     if !archive.exists() {
@@ -136,7 +291,19 @@ fn cmd_self_import(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
     // Ensure directories exist
     crate::paths::ensure_directories()?;
 
-    let self_dir = crate::paths::zircon_root().join("self");
+    // A failed import must not leave Zircon without a working binary
+    with_self_backup(|| extract_and_link_self(archive))?;
+
+    success!("✓ Zircon imported successfully!");
+    status!("  Location: {}", crate::paths::self_dir().display());
+
+    Ok(())
+}
+
+/// Replace the self directory with the contents of an archive and link its
+/// Zircon binary
+fn extract_and_link_self(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let self_dir = crate::paths::self_dir();
 
     // Remove existing self directory if it exists
     if self_dir.exists() {
@@ -147,8 +314,8 @@ fn cmd_self_import(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
     fs::create_dir_all(&self_dir)?;
 
     // Extract archive to self directory
-    println!("Extracting archive...");
-    extract_self_archive(archive, &self_dir)?;
+    status!("Extracting archive...");
+    toolchain_cmds::extract_archive(archive, &self_dir)?;
 
     // Validate that bin directory exists
     let self_bin_dir = self_dir.join("bin");
@@ -175,6 +342,8 @@ fn cmd_self_import(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
         .into());
     }
 
+    check_self_binary_platform(&zircon_binary)?;
+
     // Make executable on Unix
     #[cfg(unix)]
     {
@@ -188,38 +357,225 @@ fn cmd_self_import(archive: &std::path::Path) -> Result<(), Box<dyn Error>> {
     let zircon_link = crate::paths::zircon_binary_link();
     crate::paths::create_link(&zircon_binary, &zircon_link)?;
 
-    println!("✓ Zircon imported successfully!");
-    println!("  Location: {}", self_dir.display());
+    Ok(())
+}
+
+/// Check that a Zircon binary from an archive was built for this host
+///
+/// A wrong-arch archive (e.g. from a misconfigured mirror) would otherwise
+/// replace a working Zircon with one that can't run. Binaries whose header
+/// isn't recognized only get a warning.
+fn check_self_binary_platform(zircon_binary: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let (host_platform, host_arch) = platform::host_platform_and_arch();
+
+    match platform::detect_binary_platform(zircon_binary) {
+        Some((binary_platform, binary_arch))
+            if binary_platform != host_platform || binary_arch != host_arch =>
+        {
+            Err(format!(
+                "The Zircon binary in the archive is built for {}-{}, but this host is {}-{}.\nUse the zircon-{}-{} archive instead.",
+                binary_platform, binary_arch, host_platform, host_arch, host_platform, host_arch
+            )
+            .into())
+        }
+        Some(_) => Ok(()),
+        None => {
+            warning!(
+                "⚠ Could not tell which platform {} was built for; installing it anyway",
+                zircon_binary.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Run a step that replaces the Zircon binary, restoring the previous one if
+/// it fails
+///
+/// The binary `bin/zircon` points at is copied aside first. If the step
+/// succeeds, the copy becomes `zircon.old` for `zircon self rollback`; if it
+/// fails, the copy is put back and `zircon.old` is left as it was.
+fn with_self_backup(
+    replace: impl FnOnce() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let Some((active, pending)) = backup_self_binary()? else {
+        // Nothing to lose, e.g. on the first `self import`
+        return replace();
+    };
+
+    if let Err(e) = replace() {
+        match restore_self_binary(&pending, &active) {
+            Ok(()) => {
+                fs::remove_file(&pending).ok();
+                warning!("⚠ Restored the previous Zircon binary");
+            }
+            Err(restore_err) => warning!(
+                "⚠ Failed to restore the previous Zircon binary: {}\nA copy is kept at {}.",
+                restore_err,
+                pending.display()
+            ),
+        }
+        return Err(e);
+    }
+
+    fs::rename(&pending, crate::paths::self_backup_binary())?;
+    Ok(())
+}
+
+/// Copy the binary `bin/zircon` points at next to `zircon.old`
+///
+/// Returns the path of that binary and of the copy, or `None` if there is no
+/// binary.
+fn backup_self_binary() -> Result<Option<(std::path::PathBuf, std::path::PathBuf)>, Box<dyn Error>>
+{
+    let Ok(active) = crate::paths::zircon_binary_link().canonicalize() else {
+        return Ok(None);
+    };
+    if !active.is_file() {
+        return Ok(None);
+    }
+
+    let mut pending = crate::paths::self_backup_binary().into_os_string();
+    pending.push(".pending");
+    let pending = std::path::PathBuf::from(pending);
+    verbose!("Backing up {} to {}", active.display(), pending.display());
+    fs::copy(&active, &pending)?;
+
+    Ok(Some((active, pending)))
+}
+
+/// Put a backed-up binary back at `active` and point `bin/zircon` at it
+fn restore_self_binary(
+    backup: &std::path::Path,
+    active: &std::path::Path,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = active.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    replace_file(backup, active)?;
+    crate::paths::create_link(active, &crate::paths::zircon_binary_link())?;
+    Ok(())
+}
+
+/// Replace `dest` with a copy of `src`
+///
+/// The copy is renamed into place. On Unix that works even while `dest` is
+/// running. Windows refuses to replace a running executable but lets it be
+/// renamed, so there `dest` is moved aside first (see [`move_aside`]).
+fn replace_file(src: &std::path::Path, dest: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let mut temp = dest.as_os_str().to_owned();
+    temp.push(".new");
+    fs::copy(src, &temp)?;
+
+    #[cfg(windows)]
+    let aside = move_aside(dest).inspect_err(|_| {
+        fs::remove_file(&temp).ok();
+    })?;
+    if let Err(e) = fs::rename(&temp, dest) {
+        #[cfg(windows)]
+        if let Some(aside) = &aside {
+            fs::rename(aside, dest).ok();
+        }
+        fs::remove_file(&temp).ok();
+        return Err(e.into());
+    }
+    // This fails while `dest` is still running; the next replacement retries
+    #[cfg(windows)]
+    if let Some(aside) = aside {
+        fs::remove_file(aside).ok();
+    }
+    Ok(())
+}
+
+/// Rename `dest` to `<dest>.running` so it can be replaced even if it is the
+/// running executable
+///
+/// A file left there by an earlier replacement is removed first. Returns the
+/// new path, or `None` if `dest` doesn't exist.
+#[cfg(windows)]
+fn move_aside(dest: &std::path::Path) -> std::io::Result<Option<std::path::PathBuf>> {
+    let mut aside = dest.as_os_str().to_owned();
+    aside.push(".running");
+    let aside = std::path::PathBuf::from(aside);
+
+    if aside.exists() {
+        fs::remove_file(&aside)?;
+    }
+    if !dest.exists() {
+        return Ok(None);
+    }
+    fs::rename(dest, &aside)?;
+    Ok(Some(aside))
+}
+
+/// Swap the active Zircon binary with the backup in `zircon.old`
+///
+/// Running it again swaps them back.
+fn cmd_self_rollback() -> Result<(), Box<dyn Error>> {
+    use crate::paths;
+
+    let backup = paths::self_backup_binary();
+    if !backup.is_file() {
+        return Err(format!(
+            "No previous Zircon binary found at {}.\nA backup is made when 'zircon self build', 'update', 'import' or 'install' replaces Zircon.",
+            backup.display()
+        )
+        .into());
+    }
+
+    let zircon_link = paths::zircon_binary_link();
+    let active = zircon_link.canonicalize().map_err(|e| {
+        format!(
+            "{} is missing or broken ({}).\nRun 'zircon repair' first.",
+            zircon_link.display(),
+            e
+        )
+    })?;
+
+    let mut swap = backup.as_os_str().to_owned();
+    swap.push(".swap");
+    fs::copy(&active, &swap)?;
+    replace_file(&backup, &active)?;
+    fs::rename(&swap, &backup)?;
+    paths::create_link(&active, &zircon_link)?;
+
+    success!("✓ Rolled back to the previous Zircon binary");
+    status!("  Run 'zircon self rollback' again to undo.");
 
     Ok(())
 }
 
 /// Install a pre-built Zircon release
-fn cmd_self_install(tag: &str) -> Result<(), Box<dyn Error>> {
+fn cmd_self_install(
+    tag: &str,
+    options: &crate::download::DownloadOptions,
+) -> Result<(), Box<dyn Error>> {
     use std::env;
 
-    println!("Installing Zircon {} release...", tag);
+    status!("Installing Zircon {} release...", tag);
 
     // Detect platform and architecture
-    let (platform, arch) = detect_platform_and_arch()?;
+    let artifact_name = platform::get_platform_artifact_name()?;
 
     // Construct download URL for zircon repository
-    let filename = format!("zircon-{}-{}.tar.gz", platform, arch);
-    let url = format!(
-        "https://github.com/zirco-lang/zircon/releases/download/{}/{}",
-        tag, filename
-    );
-
-    println!("Downloading from: {}", url);
+    let filename = format!("zircon-{}.{}", artifact_name, platform::archive_extension());
+    let url = crate::download::release_url("zircon", tag, &filename)?;
 
     // Create temporary directory for download
     let temp_dir = env::temp_dir();
     let temp_file = temp_dir.join(&filename);
 
     // Download the file
-    download_file(&url, &temp_file)?;
+    let client = crate::download::client(options)?;
+    crate::download::download_release_archive(
+        &client,
+        &url,
+        &crate::download::cache_path(tag, &filename),
+        &temp_file,
+        options,
+    )?;
 
-    println!("Download complete. Importing Zircon...");
+    status!("Download complete. Importing Zircon...");
 
     // Import the downloaded archive
     let result = cmd_self_import(&temp_file);
@@ -228,147 +584,126 @@ fn cmd_self_install(tag: &str) -> Result<(), Box<dyn Error>> {
     if temp_file.exists()
         && let Err(e) = fs::remove_file(&temp_file)
     {
-        eprintln!("Warning: Failed to clean up temporary file: {}", e);
+        warning!("Warning: Failed to clean up temporary file: {}", e);
     }
 
     result
 }
 
-/// Extract archive to self directory (supports tar.gz, tar, and zip)
-fn extract_self_archive(
-    archive_path: &std::path::Path,
-    dest_dir: &std::path::Path,
-) -> Result<(), Box<dyn Error>> {
-    use flate2::read::GzDecoder;
-    use std::fs::File;
+/// Entries Zircon may create directly inside its root directory
+///
+/// `self uninstall` refuses to run if the root contains anything else, so a
+/// misconfigured `ZIRCON_PREFIX` can't wipe unrelated files.
+const MANAGED_ROOT_ENTRIES: &[&str] = &[
+    "bin",
+    "cache",
+    "config.toml",
+    "logs",
+    "overrides.toml",
+    "self",
+    "sources",
+    "toolchains",
+    "zircon.lock",
+    "zircon.old",
+    "zircon.old.exe",
+    "zircon.old.pending",
+    "zircon.old.exe.pending",
+    "zircon.old.swap",
+    "zircon.old.exe.swap",
+    ".last_update_check",
+    ".latest_release.json",
+];
+
+/// Remove the Zircon installation
+fn cmd_self_uninstall(yes: bool, keep_toolchains: bool) -> Result<(), Box<dyn Error>> {
     use std::io;
-    use tar::Archive;
-    use zip::ZipArchive;
-
-    // Determine archive type by checking the full filename first
-    let filename = archive_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("");
-
-    let filename_lower = filename.to_lowercase();
-
-    // Check for multi-part extensions first
-    if filename_lower.ends_with(".tar.gz")
-        || std::path::Path::new(&filename_lower)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("tgz"))
+
+    use crate::paths;
+
+    let zircon_root = paths::zircon_root();
+
+    if !zircon_root.exists() {
+        return Err(format!("No Zircon installation found at {}", zircon_root.display()).into());
+    }
+    // Windows can't delete the lock file while it is held, so the lock is
+    // released just before anything is removed
+    let lock = crate::lock::acquire()?;
+
+    // Refuse to delete anything that looks like it isn't a dedicated Zircon directory
+    let zircon_root = zircon_root.canonicalize()?;
+    let home = dirs::home_dir().and_then(|home| home.canonicalize().ok());
+    if zircon_root.parent().is_none()
+        || home
+            .as_ref()
+            .is_some_and(|home| home.starts_with(&zircon_root))
     {
-        let file = File::open(archive_path)?;
-        let decoder = GzDecoder::new(file);
-        let mut archive = Archive::new(decoder);
-        archive.unpack(dest_dir)?;
+        return Err(format!(
+            "Refusing to uninstall: the Zircon root {} is a system or home directory.\nCheck your ZIRCON_PREFIX setting.",
+            zircon_root.display()
+        )
+        .into());
+    }
+
+    let unmanaged: Vec<String> = fs::read_dir(&zircon_root)?
+        .filter_map(Result::ok)
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| !MANAGED_ROOT_ENTRIES.contains(&name.as_str()))
+        .collect();
+    if !unmanaged.is_empty() {
+        return Err(format!(
+            "Refusing to uninstall: {} contains files not managed by Zircon:\n  {}\nCheck your ZIRCON_PREFIX setting, or remove these files first.",
+            zircon_root.display(),
+            unmanaged.join("\n  ")
+        )
+        .into());
+    }
+
+    if keep_toolchains {
+        println!(
+            "This will remove Zircon from {} but keep installed toolchains.",
+            zircon_root.display()
+        );
     } else {
-        // Fall back to single extension check
-        let extension = archive_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("");
-
-        match extension {
-            "zip" => {
-                let file = File::open(archive_path)?;
-                let mut archive = ZipArchive::new(file)?;
-
-                for i in 0..archive.len() {
-                    let mut file = archive.by_index(i)?;
-                    let outpath = match file.enclosed_name() {
-                        Some(path) => dest_dir.join(path),
-                        None => continue,
-                    };
-
-                    if file.name().ends_with('/') {
-                        fs::create_dir_all(&outpath)?;
-                    } else {
-                        if let Some(parent) = outpath.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-                        let mut outfile = File::create(&outpath)?;
-                        io::copy(&mut file, &mut outfile)?;
-                    }
-
-                    // Preserve Unix permissions on Unix systems
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        if let Some(mode) = file.unix_mode() {
-                            fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-                        }
-                    }
-                }
-            }
-            "tar" => {
-                let file = File::open(archive_path)?;
-                let mut archive = Archive::new(file);
-                archive.unpack(dest_dir)?;
-            }
-            _ => {
-                return Err(
-                    "Unsupported archive format. Supported formats: .tar.gz, .tgz, .tar, .zip"
-                        .into(),
-                );
-            }
-        }
+        println!(
+            "This will remove {} and all installed toolchains.",
+            zircon_root.display()
+        );
     }
 
-    Ok(())
-}
+    if !yes {
+        println!("\nProceed with uninstall? (y/N): ");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
 
-/// Detect the current platform and architecture
-fn detect_platform_and_arch() -> Result<(String, String), Box<dyn Error>> {
-    let os = std::env::consts::OS;
-    let arch = std::env::consts::ARCH;
-
-    let platform = match os {
-        "linux" => "linux",
-        "macos" => "macos",
-        _ => {
-            return Err(format!(
-                "Unsupported platform: {}. Only linux and macos are supported.",
-                os
-            )
-            .into());
+        if input != "y" && input != "yes" {
+            println!("Cancelled.");
+            return Ok(());
         }
-    };
+    }
 
-    let architecture = match arch {
-        "x86_64" => "x64",
-        "aarch64" => "arm64",
-        _ => {
-            return Err(format!(
-                "Unsupported architecture: {}. Only x86_64 (x64) and aarch64 (arm64) are supported.",
-                arch
-            )
-            .into());
+    drop(lock);
+    if keep_toolchains {
+        for entry in fs::read_dir(&zircon_root)? {
+            let entry = entry?;
+            if entry.file_name() == "toolchains" {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() && path.read_link().is_err() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
         }
-    };
-
-    Ok((platform.to_string(), architecture.to_string()))
-}
-
-/// Download a file from a URL to a local path
-fn download_file(url: &str, dest: &std::path::PathBuf) -> Result<(), Box<dyn Error>> {
-    use std::fs::File;
-    use std::io::Write;
-
-    let response = reqwest::blocking::get(url)?;
-
-    if !response.status().is_success() {
-        return Err(format!(
-            "Failed to download file: HTTP {}. The release may not be available or may not have pre-built binaries for your platform.",
-            response.status()
-        )
-        .into());
+    } else {
+        fs::remove_dir_all(&zircon_root)?;
     }
 
-    let mut file = File::create(dest)?;
-    let content = response.bytes()?;
-    file.write_all(&content)?;
+    success!("✓ Zircon uninstalled");
+    status!("\nRemove these lines from your shell profile (~/.bashrc, ~/.zshrc, etc.) if present:");
+    status!("  export PATH=\"{}:$PATH\"", paths::bin_dir().display());
+    status!("  source <(zircon env)");
 
     Ok(())
 }
@@ -0,0 +1,103 @@
+//! Shims that run a tool from the active toolchain
+//!
+//! A shim is a link to the zircon binary named after a toolchain tool (e.g.
+//! `~/.zircon/bin/zrc`). When zircon is started under one of those names it
+//! runs the tool from the active toolchain instead of its own CLI, so switching
+//! toolchains takes effect without re-sourcing `zircon env`.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+use clap::Parser;
+
+use crate::output::{status, success};
+use crate::{cli::DispatchCommand, paths, toolchains};
+
+/// Toolchain tools that get a shim
+pub const SHIM_NAMES: &[&str] = &["zrc", "zircop"];
+
+/// Install shims for the toolchain's tools into Zircon's bin directory
+#[derive(Parser)]
+pub struct ShimsCmd;
+
+impl DispatchCommand for ShimsCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        // Link to the stable `bin/zircon` link where possible, so the shims
+        // keep working after Zircon updates itself
+        let zircon_link = paths::zircon_binary_link();
+        let zircon_binary = if zircon_link.exists() {
+            zircon_link
+        } else {
+            std::env::current_exe()?
+        };
+
+        std::fs::create_dir_all(paths::bin_dir())?;
+
+        for name in SHIM_NAMES {
+            let shim = paths::bin_dir().join(if cfg!(windows) {
+                format!("{}.exe", name)
+            } else {
+                (*name).to_string()
+            });
+            paths::create_link(&zircon_binary, &shim)?;
+            status!("  {} -> {}", shim.display(), zircon_binary.display());
+        }
+
+        success!("✓ Installed shims for {}", SHIM_NAMES.join(", "));
+        status!(
+            "\nMake sure {} is on your PATH ahead of any other zrc.",
+            paths::bin_dir().display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Get the tool this process was started as, if it was started through a shim
+pub fn invoked_shim_name() -> Option<String> {
+    let argv0 = std::env::args_os().next()?;
+    let name = Path::new(&argv0).file_stem()?.to_str()?;
+    SHIM_NAMES.contains(&name).then(|| name.to_string())
+}
+
+/// Run a tool from the active toolchain with the given arguments
+///
+/// Exits with the tool's exit code if it fails.
+pub fn run_shim(name: &str, args: &[OsString]) -> Result<(), Box<dyn Error>> {
+    let version = toolchains::get_active_toolchain()?.ok_or_else(|| {
+        format!(
+            "No active toolchain to run {} from.\nUse 'zircon switch <version>' to select one.",
+            name
+        )
+    })?;
+
+    let binary = paths::toolchain_dir(&version)
+        .join("bin")
+        .join(if cfg!(windows) {
+            format!("{}.exe", name)
+        } else {
+            name.to_string()
+        });
+
+    if !binary.exists() {
+        return Err(format!(
+            "{} not found in toolchain '{}' at {}",
+            name,
+            version,
+            binary.display()
+        )
+        .into());
+    }
+
+    // stdin/stdout/stderr are inherited by default
+    let status = Command::new(&binary).args(args).status()?;
+
+    if !status.success() {
+        // Propagate the exit code of the tool as our own
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
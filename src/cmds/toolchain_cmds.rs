@@ -3,107 +3,432 @@
 use std::error::Error;
 use std::fs::File;
 use std::io::{self, Read};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
 
-use clap::Parser;
+use bzip2::read::BzDecoder;
+use clap::{Parser, ValueEnum};
+use flate2::Compression;
 use flate2::read::GzDecoder;
-use sha2::{Digest, Sha256};
-use tar::Archive;
-use zip::ZipArchive;
+use flate2::write::GzEncoder;
+use globset::Glob;
+use owo_colors::AnsiColors;
+use tar::{Archive, Builder};
+use xz2::read::XzDecoder;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+use zstd::stream::read::Decoder as ZstdDecoder;
 
-use crate::{cli::DispatchCommand, paths, toolchains};
+use crate::cmds::build_cmds;
+use crate::manifest::{self, Manifest, ToolchainSource};
+use crate::output::{self, Stream, status, success, warning};
+use crate::{checksum, cli::DispatchCommand, download, duration, git_utils, paths, toolchains};
 
 /// Switch to a different installed toolchain version
 #[derive(Parser)]
 pub struct SwitchCmd {
-    /// The version to switch to
+    /// The version to switch to, or `-` for the previously active toolchain
     pub version: String,
+
+    /// Build the version from the zrc repository if it isn't installed
+    #[arg(long)]
+    pub create_if_missing: bool,
+
+    /// Switch even if the toolchain was built for another platform
+    #[arg(long)]
+    pub force: bool,
 }
 
 impl DispatchCommand for SwitchCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        let toolchain_dir = paths::toolchain_dir(&self.version);
+        let version = if self.version == "-" {
+            toolchains::get_previous_toolchain()?.ok_or(
+                "No previous toolchain recorded.\nUse 'zircon switch <version>' to switch toolchains first.",
+            )?
+        } else {
+            self.version
+        };
 
-        if !toolchains::toolchain_exists(&self.version) {
+        let toolchain_dir = paths::toolchain_dir(&version);
+
+        if !toolchains::toolchain_exists(&version) && self.create_if_missing {
+            return build_missing_toolchain(&version);
+        }
+
+        if !toolchains::toolchain_exists(&version) {
             return Err(format!(
                 "Toolchain '{}' not found at {}\nUse 'zircon build {}' to install it.",
-                self.version,
+                version,
                 toolchain_dir.display(),
-                self.version
+                version
             )
             .into());
         }
 
+        if let Some(mismatch) = Manifest::read(&toolchain_dir).host_mismatch() {
+            if !self.force {
+                return Err(format!(
+                    "Toolchain '{}' won't run here: {}.\nPass --force to switch to it anyway.",
+                    version, mismatch
+                )
+                .into());
+            }
+            warning!(
+                "⚠ Toolchain '{}' won't run here: {}; switching anyway",
+                version,
+                mismatch
+            );
+        }
+
+        let previous = toolchains::get_current_toolchain()?;
+
         // Update current symlink
         let current_link = paths::current_toolchain_link();
         paths::create_link(&toolchain_dir, &current_link)?;
 
-        println!("✓ Switched to toolchain: {}", self.version);
+        // Remember where we came from so `zircon switch -` can go back
+        if let Some(previous) = previous
+            && previous != version
+        {
+            toolchains::set_previous_toolchain(&previous)?;
+        }
+
+        success!("✓ Switched to toolchain: {}", version);
+
+        Ok(())
+    }
+}
+
+/// Build a toolchain that `zircon switch --create-if-missing` didn't find
+///
+/// The build switches to the new toolchain itself.
+fn build_missing_toolchain(version: &str) -> Result<(), Box<dyn Error>> {
+    // Branch builds are named after the commit they were built from, which
+    // can't be rebuilt by name
+    if version.contains('@') {
+        return Err(format!(
+            "Toolchain '{}' not found, and it names a build of a branch at a specific commit.\nUse 'zircon build <branch>' to build the branch again.",
+            version
+        )
+        .into());
+    }
+    // Debug builds get a `-debug` suffix
+    let (name, debug) = version
+        .strip_suffix("-debug")
+        .map_or((version, false), |name| (name, true));
+    let reference = git_utils::ref_name_from_toolchain_name(name);
+    if !git2::Reference::is_valid_name(&format!("refs/heads/{}", reference)) {
+        return Err(format!(
+            "Toolchain '{}' not found, and '{}' is not a git reference that can be built.",
+            version, reference
+        )
+        .into());
+    }
+
+    status!("Toolchain '{}' not found; building it", version);
+    let previous = toolchains::get_current_toolchain()?;
+
+    let mut build_args = vec!["zircon build", reference.as_str()];
+    if debug {
+        build_args.push("--debug");
+    }
+    build_cmds::BuildCmd::try_parse_from(build_args)?.dispatch()?;
+
+    if let Some(previous) = previous {
+        toolchains::set_previous_toolchain(&previous)?;
+    }
+
+    Ok(())
+}
+
+/// Run a command from a specific toolchain without switching
+#[derive(Parser)]
+pub struct RunCmd {
+    /// The version to run
+    pub version: String,
+
+    /// Arguments passed verbatim to zrc (after `--`)
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+impl DispatchCommand for RunCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        if !toolchains::toolchain_exists(&self.version) {
+            return Err(format!(
+                "Toolchain '{}' not found.\nUse 'zircon list' to see installed toolchains.",
+                self.version
+            )
+            .into());
+        }
+
+        let zrc_binary = paths::toolchain_dir(&self.version)
+            .join("bin")
+            .join(if cfg!(windows) { "zrc.exe" } else { "zrc" });
+
+        if !zrc_binary.exists() {
+            return Err(format!(
+                "zrc binary not found in toolchain '{}' at {}",
+                self.version,
+                zrc_binary.display()
+            )
+            .into());
+        }
+
+        // stdin/stdout/stderr are inherited by default
+        let status = Command::new(&zrc_binary).args(&self.args).status()?;
+
+        if !status.success() {
+            // Propagate the exit code of zrc as our own
+            std::process::exit(status.code().unwrap_or(1));
+        }
 
         Ok(())
     }
 }
 
+/// How an archive is compressed and packed
+#[derive(Debug, Clone, Copy)]
+enum ArchiveFormat {
+    /// Gzipped tarball
+    TarGz,
+    /// xz-compressed tarball
+    TarXz,
+    /// bzip2-compressed tarball
+    TarBz2,
+    /// zstd-compressed tarball
+    TarZst,
+    /// Plain tarball
+    Tar,
+    /// Zip archive
+    Zip,
+}
+
+/// Archive formats accepted by `zircon import` and `zircon self import`, by
+/// the suffix of the lowercased filename
+const ARCHIVE_FORMATS: &[(&str, ArchiveFormat)] = &[
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".tar.xz", ArchiveFormat::TarXz),
+    (".txz", ArchiveFormat::TarXz),
+    (".tar.bz2", ArchiveFormat::TarBz2),
+    (".tbz2", ArchiveFormat::TarBz2),
+    (".tar.zst", ArchiveFormat::TarZst),
+    (".tzst", ArchiveFormat::TarZst),
+    (".tar", ArchiveFormat::Tar),
+    (".zip", ArchiveFormat::Zip),
+];
+
+/// List the accepted archive suffixes, for error messages
+fn supported_formats() -> String {
+    ARCHIVE_FORMATS
+        .iter()
+        .map(|(suffix, _)| *suffix)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Import a toolchain from an archive file
 #[derive(Parser)]
-#[command(about = "Import a toolchain from an archive (.tar.gz, .tar, or .zip)")]
+#[command(
+    about = "Import a toolchain from an archive (.tar.gz, .tar.xz, .tar.bz2, .tar.zst, .tar, or .zip)"
+)]
 pub struct ImportCmd {
-    /// Path to the archive (.tar.gz, .tar, or .zip) containing the toolchain
+    /// Path or http(s):// URL of the archive (.tar.gz, .tar.xz, .tar.bz2, .tar.zst, .tar, or .zip) containing the toolchain
     pub archive: PathBuf,
+
+    /// Name to give the toolchain instead of deriving one from the archive
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Replace the toolchain if it already exists
+    #[arg(long)]
+    pub force: bool,
+
+    /// Abort unless the archive's SHA256 digest matches this hex string
+    #[arg(long, value_name = "HEX")]
+    pub verify_sha256: Option<String>,
+
+    /// Allow downloading the archive over plain HTTP
+    #[arg(long)]
+    pub allow_insecure: bool,
 }
 
 impl DispatchCommand for ImportCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        // Verify archive exists
-        if !self.archive.exists() {
-            return Err(format!("Archive not found: {}", self.archive.display()).into());
-        }
+        let manifest = Manifest::new(
+            ToolchainSource::Import,
+            Some(self.archive.display().to_string()),
+            None,
+        );
+        let options = ImportOptions {
+            name: self.name,
+            force: self.force,
+            expected_sha256: self.verify_sha256,
+        };
+        let download_options = download::DownloadOptions {
+            allow_insecure: self.allow_insecure,
+            ..Default::default()
+        };
+        download::with_local_archive(&self.archive, &download_options, |archive| {
+            import_archive(archive, manifest, &options)
+        })
+    }
+}
 
-        // Compute hash of the archive
-        let hash = compute_archive_hash(&self.archive)?;
+/// Options controlling how an archive is imported as a toolchain
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Use this name for the toolchain instead of `<archive name>-<hash>`
+    pub name: Option<String>,
+    /// Replace an existing toolchain of the same name instead of failing
+    pub force: bool,
+    /// Require the archive to have this SHA256 digest
+    pub expected_sha256: Option<String>,
+}
 
-        // Extract base name from archive filename and append hash
-        let base_name = extract_version_from_filename(&self.archive)?;
-        let version = format!("{}-{}", base_name, hash);
+/// Import a toolchain from an archive and make it the current toolchain
+///
+/// The manifest is written into the new toolchain directory once the archive
+/// has been extracted.
+pub fn import_archive(
+    archive: &Path,
+    mut manifest: Manifest,
+    options: &ImportOptions,
+) -> Result<(), Box<dyn Error>> {
+    // Verify archive exists
+    if !archive.exists() {
+        return Err(format!("Archive not found: {}", archive.display()).into());
+    }
 
-        println!("Importing toolchain: {}", version);
+    let digest = checksum::sha256_file(archive)?;
 
-        // Check if toolchain already exists
-        if toolchains::toolchain_exists(&version) {
+    // Check the archive before anything is extracted from it
+    if let Some(expected) = &options.expected_sha256 {
+        if !checksum::digests_match(expected, &digest) {
             return Err(format!(
-                "Toolchain '{}' already exists.\nUse 'zircon delete {}' to remove it first.",
-                version, version
+                "SHA256 mismatch for {}.\n  Expected: {}\n  Actual:   {}\nThe archive may be corrupt or tampered with.",
+                archive.display(),
+                expected.trim(),
+                digest
             )
             .into());
         }
+        success!("✓ Checksum verified");
+    }
 
-        // Ensure directories exist
-        paths::ensure_directories()?;
+    let version = if let Some(name) = &options.name {
+        toolchains::validate_toolchain_name(name)?;
+        name.clone()
+    } else {
+        // Extract base name from archive filename and append a hash of the archive
+        let base_name = extract_version_from_filename(archive)?;
+        archive_version_name(&base_name, &digest)
+    };
+    manifest.archive_sha256 = Some(digest);
 
-        // Create toolchain directory
-        let toolchain_dir = paths::toolchain_dir(&version);
-        std::fs::create_dir_all(&toolchain_dir)?;
+    status!("Importing toolchain: {}", version);
+
+    // Check if toolchain already exists
+    let replacing = toolchains::toolchain_exists(&version);
+    if replacing && !options.force {
+        return Err(format!(
+            "Toolchain '{}' already exists.\nUse 'zircon delete {}' to remove it first, or pass --force to replace it.",
+            version, version
+        )
+        .into());
+    }
+
+    // Ensure directories exist
+    paths::ensure_directories()?;
 
+    // Extract next to the toolchain directory, so an existing toolchain is
+    // only replaced once the new one is complete. Hidden entries aren't
+    // listed as toolchains.
+    let toolchain_dir = paths::toolchain_dir(&version);
+    let staging_dir =
+        toolchain_dir.with_file_name(format!(".{}.import-{}", version, std::process::id()));
+    if staging_dir.exists() {
+        std::fs::remove_dir_all(&staging_dir)?;
+    }
+    std::fs::create_dir_all(&staging_dir)?;
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
         // Extract archive
-        println!("Extracting archive...");
-        extract_archive(&self.archive, &toolchain_dir)?;
+        status!("Extracting archive...");
+        extract_archive(archive, &staging_dir)?;
 
         // Validate toolchain structure
-        validate_toolchain_structure(&toolchain_dir)?;
+        validate_toolchain_structure(&staging_dir)?;
 
-        println!("✓ Successfully imported toolchain: {}", version);
-        println!("  Toolchain location: {}", toolchain_dir.display());
+        // Record where the toolchain came from
+        manifest.record_binary_platform(&staging_dir);
+        manifest.record_checksums(&staging_dir)?;
+        manifest.write(&staging_dir)?;
 
-        // Always set as current
-        let current_link = paths::current_toolchain_link();
-        paths::create_link(&toolchain_dir, &current_link)?;
-        println!("✓ Set as current toolchain");
+        if replacing {
+            if toolchains::get_current_toolchain()?.as_deref() == Some(version.as_str()) {
+                warning!(
+                    "Warning: Replacing the current toolchain '{}'; shells using it may break until the import finishes",
+                    version
+                );
+            }
+            status!("Replacing existing toolchain: {}", version);
+            // Move the old toolchain aside, so it can be put back if the new
+            // one can't be moved into place
+            let old_dir =
+                toolchain_dir.with_file_name(format!(".{}.old-{}", version, std::process::id()));
+            std::fs::rename(&toolchain_dir, &old_dir)?;
+            if let Err(e) = std::fs::rename(&staging_dir, &toolchain_dir) {
+                std::fs::rename(&old_dir, &toolchain_dir)?;
+                return Err(e.into());
+            }
+            if let Err(e) = std::fs::remove_dir_all(&old_dir) {
+                warning!(
+                    "Warning: Failed to remove the replaced toolchain at {}: {}",
+                    old_dir.display(),
+                    e
+                );
+            }
+        } else {
+            std::fs::rename(&staging_dir, &toolchain_dir)?;
+        }
+        Ok(())
+    })();
 
-        println!("\nTo use this toolchain, run:");
-        println!("  source <(zircon env)");
+    // Don't leave a half-extracted toolchain behind
+    if let Err(e) = result {
+        if let Err(remove_err) = std::fs::remove_dir_all(&staging_dir) {
+            warning!(
+                "Warning: Failed to remove partially imported toolchain at {}: {}",
+                staging_dir.display(),
+                remove_err
+            );
+        }
+        return Err(e);
+    }
 
-        Ok(())
+    success!("✓ Successfully imported toolchain: {}", version);
+    status!("  Toolchain location: {}", toolchain_dir.display());
+
+    if let Some(mismatch) = manifest.host_mismatch() {
+        warning!(
+            "⚠ Toolchain '{}' won't run here: {}.\nImport the archive for this platform instead.",
+            version,
+            mismatch
+        );
     }
+
+    // Always set as current
+    let current_link = paths::current_toolchain_link();
+    paths::create_link(&toolchain_dir, &current_link)?;
+    success!("✓ Set as current toolchain");
+
+    status!("\nTo use this toolchain, run:");
+    status!("  source <(zircon env)");
+
+    Ok(())
 }
 
 /// Extract version name from archive filename
@@ -117,6 +442,12 @@ fn extract_version_from_filename(path: &Path) -> Result<String, Box<dyn Error>>
     let name = filename
         .trim_end_matches(".tar.gz")
         .trim_end_matches(".tgz")
+        .trim_end_matches(".tar.xz")
+        .trim_end_matches(".txz")
+        .trim_end_matches(".tar.bz2")
+        .trim_end_matches(".tbz2")
+        .trim_end_matches(".tar.zst")
+        .trim_end_matches(".tzst")
         .trim_end_matches(".tar")
         .trim_end_matches(".zip");
 
@@ -127,70 +458,161 @@ fn extract_version_from_filename(path: &Path) -> Result<String, Box<dyn Error>>
     Ok(name.to_string())
 }
 
-/// Compute a short hash of the archive file for uniqueness
-fn compute_archive_hash(path: &Path) -> Result<String, Box<dyn Error>> {
-    let mut file = File::open(path)?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 8192];
+/// Name a toolchain imported from an archive `<base name>-<hash>`
+///
+/// The hash is the first 8 characters of the archive's digest. If a different
+/// archive (according to the digest recorded in its manifest) was already
+/// imported under that name, a longer prefix of the digest is used so the two
+/// aren't conflated.
+fn archive_version_name(base_name: &str, digest: &str) -> String {
+    let mut version = String::new();
 
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
+    for len in [8, 16, digest.len()] {
+        version = format!("{}-{}", base_name, digest.get(..len).unwrap_or(digest));
+        if !toolchains::toolchain_exists(&version) {
             break;
         }
-        hasher.update(&buffer[..bytes_read]);
+
+        // Toolchains imported before digests were recorded can't be told
+        // apart, so they are assumed to be the same archive
+        match Manifest::read(&paths::toolchain_dir(&version)).archive_sha256 {
+            Some(existing) if !checksum::digests_match(&existing, digest) => {
+                warning!(
+                    "⚠ A different archive was already imported as '{}'; using a longer hash",
+                    version
+                );
+            }
+            Some(_) | None => break,
+        }
     }
 
-    let result = hasher.finalize();
-    // Return first 8 characters of the hex digest for a "super shortened" hash
-    Ok(format!("{:x}", result)[..8].to_string())
+    version
 }
 
-/// Extract archive to destination directory
-fn extract_archive(archive_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
-    // Determine archive type by extension
-    let extension = archive_path
-        .extension()
-        .and_then(|e| e.to_str())
+/// Extract an archive to a directory, picking the format from its filename
+///
+/// Suffixes are matched without regard to case (see [`ARCHIVE_FORMATS`]).
+pub fn extract_archive(archive_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let filename = archive_path
+        .file_name()
+        .and_then(|n| n.to_str())
         .unwrap_or("");
+    let filename_lower = filename.to_lowercase();
+
+    let format = ARCHIVE_FORMATS
+        .iter()
+        .find(|(suffix, _)| filename_lower.ends_with(suffix))
+        .map(|(_, format)| *format)
+        .ok_or_else(|| {
+            format!(
+                "Unsupported archive format: '{}'. Supported formats: {}",
+                filename,
+                supported_formats()
+            )
+        })?;
 
-    match extension {
-        "zip" => extract_zip(archive_path, dest_dir)?,
-        "gz" | "tgz" => extract_tar_gz(archive_path, dest_dir)?,
-        "tar" => extract_tar(archive_path, dest_dir)?,
-        _ => {
+    match format {
+        ArchiveFormat::TarGz => unpack_tar(GzDecoder::new(File::open(archive_path)?), dest_dir),
+        ArchiveFormat::TarXz => unpack_tar(XzDecoder::new(File::open(archive_path)?), dest_dir),
+        ArchiveFormat::TarBz2 => unpack_tar(BzDecoder::new(File::open(archive_path)?), dest_dir),
+        ArchiveFormat::TarZst => unpack_tar(ZstdDecoder::new(File::open(archive_path)?)?, dest_dir),
+        ArchiveFormat::Tar => unpack_tar(File::open(archive_path)?, dest_dir),
+        ArchiveFormat::Zip => extract_zip(archive_path, dest_dir),
+    }
+}
+
+/// Unpack a tar stream into a directory
+///
+/// Entries are unpacked one at a time so that absolute paths and `..`
+/// components, which would write outside `dest_dir`, are rejected before
+/// anything is written for them, and so that the total size can be capped
+/// (see [`ExtractBudget`]).
+pub fn unpack_tar(reader: impl Read, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut archive = Archive::new(reader);
+    let mut budget = ExtractBudget::from_env()?;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+
+        if !is_contained_path(&path) {
             return Err(format!(
-                "Unsupported archive format: '{}'. Supported formats: .tar.gz, .tgz, .tar, .zip",
-                extension
+                "Refusing to extract '{}': it would be written outside {}",
+                path.display(),
+                dest_dir.display()
             )
             .into());
         }
+
+        budget.consume(entry.size())?;
+        entry.unpack_in(dest_dir)?;
     }
 
     Ok(())
 }
 
-/// Extract gzipped tarball
-fn extract_tar_gz(tarball_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
-    let file = File::open(tarball_path)?;
-    let decoder = GzDecoder::new(file);
-    let mut archive = Archive::new(decoder);
-    archive.unpack(dest_dir)?;
-    Ok(())
+/// Environment variable that overrides the extraction size limit
+const MAX_EXTRACT_BYTES_ENV_VAR: &str = "ZIRCON_MAX_EXTRACT_BYTES";
+
+/// Default limit on the total uncompressed size of an archive (8 GiB)
+const DEFAULT_MAX_EXTRACT_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+/// Tracks the total size of files extracted from an archive so that a
+/// malicious or corrupt archive can't fill the disk
+struct ExtractBudget {
+    /// Maximum number of bytes that may be extracted
+    limit: u64,
+    /// Number of bytes extracted so far
+    used: u64,
 }
 
-/// Extract plain tarball
-fn extract_tar(tarball_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
-    let file = File::open(tarball_path)?;
-    let mut archive = Archive::new(file);
-    archive.unpack(dest_dir)?;
-    Ok(())
+impl ExtractBudget {
+    /// Create a budget using `ZIRCON_MAX_EXTRACT_BYTES`, or the default limit
+    fn from_env() -> Result<Self, Box<dyn Error>> {
+        let limit = match std::env::var(MAX_EXTRACT_BYTES_ENV_VAR) {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                format!(
+                    "Invalid {} '{}': expected a number of bytes",
+                    MAX_EXTRACT_BYTES_ENV_VAR, value
+                )
+            })?,
+            Err(_) => DEFAULT_MAX_EXTRACT_BYTES,
+        };
+        Ok(Self { limit, used: 0 })
+    }
+
+    /// Number of bytes that may still be extracted
+    const fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Account for extracted bytes, failing once the limit is exceeded
+    fn consume(&mut self, bytes: u64) -> Result<(), Box<dyn Error>> {
+        self.used = self.used.saturating_add(bytes);
+        if self.used > self.limit {
+            return Err(format!(
+                "Archive expands to more than {} ({} bytes), the extraction limit.\nSet {} to a larger number of bytes if this archive is expected to be that big.",
+                format_size(self.limit),
+                self.limit,
+                MAX_EXTRACT_BYTES_ENV_VAR
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether an archive entry path stays inside the directory it is extracted to
+fn is_contained_path(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
 }
 
 /// Extract zip file
 fn extract_zip(zip_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
     let file = File::open(zip_path)?;
     let mut archive = ZipArchive::new(file)?;
+    let mut budget = ExtractBudget::from_env()?;
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
@@ -206,7 +628,12 @@ fn extract_zip(zip_path: &Path, dest_dir: &Path) -> Result<(), Box<dyn Error>> {
                 std::fs::create_dir_all(parent)?;
             }
             let mut outfile = File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+            // The sizes in a zip's headers can lie, so count what is actually written
+            let written = io::copy(
+                &mut (&mut file).take(budget.remaining().saturating_add(1)),
+                &mut outfile,
+            )?;
+            budget.consume(written)?;
         }
 
         // Preserve Unix permissions on Unix systems
@@ -237,7 +664,141 @@ fn validate_toolchain_structure(toolchain_dir: &Path) -> Result<(), Box<dyn Erro
     // Check for include directory (optional but expected)
     let include_dir = toolchain_dir.join("include");
     if !include_dir.exists() {
-        eprintln!("Warning: 'include' directory not found in toolchain");
+        warning!("Warning: 'include' directory not found in toolchain");
+    }
+
+    Ok(())
+}
+
+/// Package an installed toolchain into an archive
+#[derive(Parser)]
+pub struct ExportCmd {
+    /// The version to export
+    pub version: String,
+
+    /// Where to write the archive (defaults to `<version>.tar.gz` or
+    /// `<version>.zip` in the current directory)
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// The archive format
+    #[arg(long, value_enum, default_value_t = ExportFormat::TarGz)]
+    pub format: ExportFormat,
+}
+
+/// Archive formats `zircon export` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A gzipped tarball
+    #[value(name = "tar.gz")]
+    TarGz,
+    /// A zip archive
+    Zip,
+}
+
+impl ExportFormat {
+    /// The file extension for archives in this format
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl DispatchCommand for ExportCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let toolchain = toolchains::get_toolchain_info(&self.version)?;
+        let output = self.output.unwrap_or_else(|| {
+            PathBuf::from(format!("{}.{}", toolchain.name, self.format.extension()))
+        });
+
+        status!(
+            "Exporting toolchain {} to {}...",
+            toolchain.name,
+            output.display()
+        );
+
+        let result = match self.format {
+            ExportFormat::TarGz => write_tar_gz(&toolchain.path, &output),
+            ExportFormat::Zip => write_zip(&toolchain.path, &output),
+        };
+        if let Err(e) = result {
+            std::fs::remove_file(&output).ok();
+            return Err(e);
+        }
+
+        let digest = checksum::write_sidecar(&output)?;
+
+        success!(
+            "✓ Exported toolchain {} to {}",
+            toolchain.name,
+            output.display()
+        );
+        status!("  SHA256: {}", digest);
+        status!(
+            "  Checksum written to {}",
+            checksum::sidecar_path(&output).display()
+        );
+        status!("\nImport it elsewhere with:");
+        status!("  zircon import {}", output.display());
+
+        Ok(())
+    }
+}
+
+/// Write the contents of a directory into a gzipped tarball
+///
+/// Symlinks are stored as links and Unix permissions are preserved.
+fn write_tar_gz(src_dir: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let encoder = GzEncoder::new(File::create(output)?, Compression::default());
+    let mut builder = Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", src_dir)?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Write the contents of a directory into a zip archive
+///
+/// Unix permissions are preserved. Symlinks are followed and their targets
+/// stored, as zip has no portable way to represent them.
+fn write_zip(src_dir: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = ZipWriter::new(File::create(output)?);
+    add_dir_to_zip(&mut writer, src_dir, "")?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Recursively add the contents of a directory to a zip archive under `prefix`
+fn add_dir_to_zip(
+    writer: &mut ZipWriter<File>,
+    dir: &Path,
+    prefix: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(std::fs::DirEntry::file_name);
+
+    for entry in entries {
+        let path = entry.path();
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let metadata = std::fs::metadata(&path)?;
+
+        let mut options = SimpleFileOptions::default();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(metadata.permissions().mode());
+        }
+
+        if metadata.is_dir() {
+            let name = format!("{}/", name);
+            writer.add_directory(name.as_str(), options)?;
+            add_dir_to_zip(writer, &path, &name)?;
+        } else {
+            writer.start_file(name.as_str(), options)?;
+            io::copy(&mut File::open(&path)?, writer)?;
+        }
     }
 
     Ok(())
@@ -245,31 +806,298 @@ fn validate_toolchain_structure(toolchain_dir: &Path) -> Result<(), Box<dyn Erro
 
 /// List installed toolchains
 #[derive(Parser)]
-pub struct ListCmd;
+pub struct ListCmd {
+    /// Only list toolchains whose name matches this shell-style pattern (e.g. 'v0.*')
+    pub pattern: Option<String>,
+
+    /// How to order the toolchains
+    #[arg(long, value_enum, default_value_t = ListSort::Name)]
+    pub sort: ListSort,
+
+    /// Print the toolchains as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Orderings for `zircon list`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListSort {
+    /// By name, with release versions in version order
+    Name,
+    /// Most recently installed first
+    Date,
+    /// Largest first
+    Size,
+}
 
 impl DispatchCommand for ListCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        let toolchains = toolchains::list_toolchains()?;
+        let mut toolchains = toolchains::list_toolchains()?;
+
+        if let Some(pattern) = &self.pattern {
+            let matcher = Glob::new(pattern)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?
+                .compile_matcher();
+            toolchains.retain(|tc| matcher.is_match(&tc.name));
+        }
+
+        // Toolchains come sorted by name
+        match self.sort {
+            ListSort::Name => {}
+            ListSort::Date => {
+                toolchains.sort_by_key(|tc| std::cmp::Reverse(tc.installed_timestamp()));
+            }
+            ListSort::Size => toolchains.sort_by_key(|tc| std::cmp::Reverse(tc.size_bytes())),
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&toolchains)?);
+            return Ok(());
+        }
+
+        let broken_current = toolchains::get_broken_current_toolchain();
 
         if toolchains.is_empty() {
+            match &self.pattern {
+                Some(pattern) => println!("No toolchains match '{}'.", pattern),
+                None => println!("No toolchains installed."),
+            }
+        } else {
+            println!("Installed toolchains:");
+
+            for tc in toolchains {
+                if tc.is_current {
+                    println!("  {} (current)", tc.name);
+                } else {
+                    println!("  {}", tc.name);
+                }
+            }
+        }
+
+        if let Some(name) = broken_current {
+            println!("  {} (current, broken)", name);
+            warning!(
+                "⚠ The current toolchain '{}' no longer exists. Run 'zircon repair' or 'zircon switch <version>' to fix it.",
+                name
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Check installed toolchains for missing or damaged files
+#[derive(Parser)]
+pub struct VerifyCmd {
+    /// The version to verify (defaults to all installed toolchains)
+    pub version: Option<String>,
+}
+
+impl DispatchCommand for VerifyCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let versions = match self.version {
+            Some(version) => vec![toolchains::get_toolchain_info(&version)?.name],
+            None => toolchains::list_toolchains()?
+                .into_iter()
+                .map(|tc| tc.name)
+                .collect(),
+        };
+
+        if versions.is_empty() {
             println!("No toolchains installed.");
             return Ok(());
         }
 
-        println!("Installed toolchains:");
-
-        for tc in toolchains {
-            if tc.is_current {
-                println!("  {} (current)", tc.name);
+        let mut failed = 0;
+        for version in &versions {
+            let problems = verify_toolchain(&paths::toolchain_dir(version));
+            if problems.is_empty() {
+                println!(
+                    "{}",
+                    output::paint(format!("✓ {}", version), AnsiColors::Green, Stream::Stdout)
+                );
             } else {
-                println!("  {}", tc.name);
+                failed += 1;
+                println!(
+                    "{}",
+                    output::paint(format!("✗ {}", version), AnsiColors::Red, Stream::Stdout)
+                );
+                for problem in problems {
+                    println!("    {}", problem);
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(format!("{} toolchain(s) failed verification", failed).into());
+        }
+
+        success!("\n✓ All {} toolchain(s) verified", versions.len());
+
+        Ok(())
+    }
+}
+
+/// Check a toolchain directory for problems, returning a description of each
+fn verify_toolchain(toolchain_dir: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if let Err(e) = validate_toolchain_structure(toolchain_dir) {
+        problems.push(e.to_string());
+    }
+
+    let zrc_binary = toolchain_dir
+        .join("bin")
+        .join(if cfg!(windows) { "zrc.exe" } else { "zrc" });
+    match std::fs::metadata(&zrc_binary) {
+        Err(e) => problems.push(format!("{}: {}", zrc_binary.display(), e)),
+        Ok(metadata) if metadata.len() == 0 => {
+            problems.push(format!("{} is empty", zrc_binary.display()));
+        }
+        Ok(metadata) => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if metadata.permissions().mode() & 0o111 == 0 {
+                    problems.push(format!("{} is not executable", zrc_binary.display()));
+                }
             }
+            #[cfg(not(unix))]
+            let _ = metadata;
+        }
+    }
+
+    // Toolchains created by older versions of Zircon have no recorded checksums
+    for (file, expected) in &Manifest::read(toolchain_dir).checksums {
+        let path = toolchain_dir.join(file);
+        match checksum::sha256_file(&path) {
+            Ok(actual) if checksum::digests_match(expected, &actual) => {}
+            Ok(_) => problems.push(format!("{} does not match its recorded checksum", file)),
+            Err(e) => problems.push(format!("{}: {}", file, e)),
+        }
+    }
+
+    problems
+}
+
+/// Show details about an installed toolchain
+#[derive(Parser)]
+pub struct InfoCmd {
+    /// The version to show
+    pub version: String,
+}
+
+impl DispatchCommand for InfoCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        let info = toolchains::get_toolchain_info(&self.version)?;
+        let manifest = info.manifest();
+        let unknown = || "unknown".to_string();
+
+        if info.is_current {
+            println!("Toolchain: {} (current)", info.name);
+        } else {
+            println!("Toolchain: {}", info.name);
+        }
+        println!("  Path:      {}", info.path.display());
+        println!("  Size:      {}", format_size(info.size_bytes()));
+        println!("  Source:    {}", manifest.source);
+        println!(
+            "  Reference: {}",
+            manifest.reference.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  Commit:    {}",
+            manifest.commit.clone().unwrap_or_else(unknown)
+        );
+        if let (Some(platform), Some(arch)) = (&manifest.platform, &manifest.arch) {
+            println!("  Platform:  {}-{}", platform, arch);
+        } else {
+            println!("  Platform:  unknown");
+        }
+        if manifest.source == ToolchainSource::Build {
+            println!(
+                "  Features:  {}",
+                manifest::describe_features(&manifest.features, manifest.no_default_features)
+            );
+        }
+        println!(
+            "  Installed: {}",
+            info.installed_timestamp().map_or_else(unknown, format_age)
+        );
+
+        let bin_dir = info.path.join("bin");
+        let mut binaries: Vec<String> = std::fs::read_dir(&bin_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        binaries.sort();
+        if !bin_dir.is_dir() {
+            println!("  Binaries:  missing bin/ directory");
+        } else if binaries.is_empty() {
+            println!("  Binaries:  none");
+        } else {
+            println!("  Binaries:  {}", binaries.join(", "));
         }
 
+        let include_dir = info.path.join("include");
+        println!(
+            "  Include:   {}",
+            if include_dir.is_dir() {
+                "present"
+            } else {
+                "missing"
+            }
+        );
+
         Ok(())
     }
 }
 
+/// Format a size in bytes for humans (e.g. `12.3 MiB`)
+#[allow(clippy::cast_precision_loss)] // only used for display
+pub fn format_size(bytes: u64) -> String {
+    /// Binary unit suffixes, smallest first
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Format a Unix timestamp as a rough age (e.g. `3 days ago`)
+fn format_age(timestamp: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let age = now.saturating_sub(timestamp);
+
+    let (amount, unit) = match age {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (age / 60, "minute"),
+        3_600..86_400 => (age / 3_600, "hour"),
+        _ => (age / 86_400, "day"),
+    };
+
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
+    }
+}
+
 /// Delete a specific toolchain
 #[derive(Parser)]
 pub struct DeleteCmd {
@@ -279,9 +1107,28 @@ pub struct DeleteCmd {
 
 impl DispatchCommand for DeleteCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        println!("Deleting toolchain: {}", self.version);
+        status!("Deleting toolchain: {}", self.version);
         toolchains::delete_toolchain(&self.version)?;
-        println!("✓ Toolchain '{}' deleted", self.version);
+        success!("✓ Toolchain '{}' deleted", self.version);
+
+        Ok(())
+    }
+}
+
+/// Rename an installed toolchain
+#[derive(Parser)]
+pub struct RenameCmd {
+    /// The toolchain to rename
+    pub old: String,
+
+    /// The new name
+    pub new: String,
+}
+
+impl DispatchCommand for RenameCmd {
+    fn dispatch(self) -> Result<(), Box<dyn Error>> {
+        toolchains::rename_toolchain(&self.old, &self.new)?;
+        success!("✓ Renamed toolchain '{}' to '{}'", self.old, self.new);
 
         Ok(())
     }
@@ -293,18 +1140,34 @@ pub struct PruneCmd {
     /// Skip confirmation prompt
     #[arg(short = 'y', long = "yes")]
     yes: bool,
+
+    /// Also keep the N most recently installed toolchains
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    keep: usize,
+
+    /// Only prune toolchains last modified longer ago than this (e.g. 30d, 2w)
+    #[arg(long, value_name = "DURATION", value_parser = duration::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// List the toolchains that would be deleted without deleting them
+    #[arg(long)]
+    dry_run: bool,
 }
 
 impl DispatchCommand for PruneCmd {
     fn dispatch(self) -> Result<(), Box<dyn Error>> {
-        let to_prune = toolchains::get_prunable_toolchains()?;
+        let to_prune = toolchains::get_prunable_toolchains(self.keep, self.older_than)?;
 
         if to_prune.is_empty() {
-            println!("No unused toolchains to prune.");
+            status!("No unused toolchains to prune.");
             return Ok(());
         }
 
-        println!("Toolchains to be deleted:");
+        if self.dry_run {
+            println!("Toolchains that would be deleted:");
+        } else {
+            println!("Toolchains to be deleted:");
+        }
         for name in &to_prune {
             println!("  {}", name);
         }
@@ -313,6 +1176,11 @@ impl DispatchCommand for PruneCmd {
             println!("\nCurrent toolchain '{}' will be kept.", current);
         }
 
+        if self.dry_run {
+            println!("\nDry run: nothing was deleted.");
+            return Ok(());
+        }
+
         if !self.yes {
             println!("\nProceed with deletion? (y/N): ");
             let mut input = String::new();
@@ -325,14 +1193,49 @@ impl DispatchCommand for PruneCmd {
             }
         }
 
-        println!("\nDeleting toolchains...");
+        status!("\nDeleting toolchains...");
         for name in &to_prune {
             toolchains::delete_toolchain(name)?;
-            println!("  ✓ Deleted {}", name);
+            success!("  ✓ Deleted {}", name);
         }
 
-        println!("\n✓ Pruned {} toolchain(s)", to_prune.len());
+        success!("\n✓ Pruned {} toolchain(s)", to_prune.len());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Archive suffixes are recognized in any case, and unknown ones list the
+    /// supported formats
+    #[test]
+    fn archive_suffixes_ignore_case() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("ZRC-LINUX-X64.TGZ");
+        let mut builder = Builder::new(GzEncoder::new(
+            File::create(&archive)?,
+            Compression::default(),
+        ));
+        let contents = b"zrc";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "bin/zrc", &contents[..])?;
+        builder.into_inner()?.finish()?;
+
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest)?;
+        extract_archive(&archive, &dest)?;
+        assert_eq!(std::fs::read(dest.join("bin/zrc"))?, contents);
 
+        let error = extract_archive(&dir.path().join("zrc.rar"), &dest)
+            .err()
+            .ok_or("a .rar archive was accepted")?;
+        assert!(error.to_string().contains(&supported_formats()));
         Ok(())
     }
 }
@@ -1,7 +1,68 @@
 //! Global configuration for Zircon
 
+use std::error::Error;
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::paths;
+
 /// Required LLVM major version for Zirco
 pub const REQUIRED_LLVM_VERSION: &str = "20";
 
-/// Full LLVM version requirement description
-pub const LLVM_VERSION_DESC: &str = "LLVM 20.x";
+/// Default server hosting release downloads
+///
+/// Can be overridden with the `ZIRCON_DIST_SERVER` environment variable.
+pub const DEFAULT_DIST_SERVER: &str = "https://github.com";
+
+/// Minisign public key used to sign official releases, if one is bundled with
+/// this build
+///
+/// Official releases aren't signed yet, so no key is bundled and `--verify`
+/// needs `ZIRCON_RELEASE_PUBKEY`. Set this once the project publishes a key.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = None;
+
+/// User configuration loaded from `config.toml` in the Zircon root
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Base URL of the release distribution server
+    pub dist_server: Option<String>,
+    /// Whether to check for Zircon updates at all
+    pub update_check: bool,
+    /// Hours between checks for Zircon updates
+    pub update_interval_hours: u64,
+    /// Default number of parallel cargo jobs for `zircon build`
+    pub jobs: Option<u32>,
+    /// Acceptable LLVM major versions, in order of preference
+    pub llvm_versions: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dist_server: None,
+            update_check: true,
+            update_interval_hours: 24,
+            jobs: None,
+            llvm_versions: vec![REQUIRED_LLVM_VERSION.to_string()],
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file, using defaults if it does not exist
+    ///
+    /// A malformed file is an error rather than being silently ignored.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let config_file = paths::config_file();
+
+        if !config_file.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&config_file)?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}:\n{}", config_file.display(), e).into())
+    }
+}
@@ -1,34 +1,199 @@
-//! LLVM and clang dependency checking
+//! LLVM, clang and build tool dependency checking
 
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::config;
+use crate::build;
+use crate::output::{error, status, success, verbose, warning};
+
+/// An LLVM installation found by [`check_llvm`]
+#[derive(Debug, Clone)]
+pub struct Llvm {
+    /// The version reported by `llvm-config --version` (e.g. `20.1.2`)
+    pub version: String,
+    /// The installation prefix reported by `llvm-config --prefix`, if any
+    pub prefix: Option<PathBuf>,
+}
+
+impl Llvm {
+    /// Describe an LLVM installation found with `llvm_config`
+    fn new(llvm_config: &str, version: &str) -> Self {
+        let prefix = Command::new(llvm_config)
+            .arg("--prefix")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|prefix| !prefix.is_empty())
+            .map(PathBuf::from);
+
+        Self {
+            version: version.to_string(),
+            prefix,
+        }
+    }
+
+    /// The variable llvm-sys reads this installation's prefix from, e.g.
+    /// `LLVM_SYS_200_PREFIX` for LLVM 20
+    pub fn prefix_env_var(&self) -> String {
+        let major = self.version.split('.').next().unwrap_or_default();
+        format!("LLVM_SYS_{}0_PREFIX", major)
+    }
+
+    /// The llvm-sys prefix variable to pass to a build, unless it is already
+    /// set in the environment
+    pub fn build_env(&self) -> Option<(String, String)> {
+        let var = self.prefix_env_var();
+        if std::env::var_os(&var).is_some() {
+            return None;
+        }
+        let prefix = self.prefix.as_ref()?;
+        Some((var, prefix.display().to_string()))
+    }
+}
+
+/// Describe a set of acceptable LLVM major versions (e.g. "LLVM 19.x or 20.x")
+pub fn llvm_versions_desc(llvm_versions: &[String]) -> String {
+    let versions: Vec<String> = llvm_versions.iter().map(|v| format!("{}.x", v)).collect();
+    match versions.split_last() {
+        None => "LLVM".to_string(),
+        Some((last, [])) => format!("LLVM {}", last),
+        Some((last, rest)) => format!("LLVM {} or {}", rest.join(", "), last),
+    }
+}
+
+/// Check whether an LLVM version string has one of the acceptable major versions
+fn is_acceptable_llvm_version(version: &str, llvm_versions: &[String]) -> bool {
+    llvm_versions
+        .iter()
+        .any(|major| version.starts_with(&format!("{}.", major)))
+}
+
+/// Get the version number from the first line of `clang --version` (e.g.
+/// `20.1.8` from `Ubuntu clang version 20.1.8 (1ubuntu1)`)
+fn clang_version_number(version_line: &str) -> Option<&str> {
+    let (_, rest) = version_line.split_once("version ")?;
+    rest.split_whitespace().next()
+}
+
+/// A clang found by [`check_clang`]
+#[derive(Debug, Clone)]
+pub struct Clang {
+    /// The first line of `clang --version`
+    pub version: String,
+    /// The clang binary, resolved against `PATH` where possible
+    pub path: PathBuf,
+}
+
+impl Clang {
+    /// Describe a clang found as `command`
+    fn new(command: &str, version: &str) -> Self {
+        Self {
+            version: version.to_string(),
+            path: find_in_path(command).unwrap_or_else(|| PathBuf::from(command)),
+        }
+    }
+
+    /// The `clang++` next to this clang (e.g. `clang++-20` for `clang-20`),
+    /// if there is one
+    pub fn cxx_path(&self) -> Option<PathBuf> {
+        let file_name = self.path.file_name()?.to_str()?;
+        let suffix = file_name.strip_prefix("clang")?;
+        let cxx = self.path.with_file_name(format!("clang++{}", suffix));
+        cxx.is_file().then_some(cxx)
+    }
+
+    /// `CC`, `CXX` and `ZIRCON_CLANG` for a build, skipping any that are
+    /// already set in the environment
+    pub fn build_env(&self) -> Vec<(String, String)> {
+        let cc = self.path.display().to_string();
+        let cxx = self.cxx_path().map(|cxx| cxx.display().to_string());
+
+        [
+            ("CC", Some(cc.clone())),
+            ("CXX", cxx),
+            ("ZIRCON_CLANG", Some(cc)),
+        ]
+        .into_iter()
+        .filter(|(var, _)| std::env::var_os(var).is_none())
+        .filter_map(|(var, value)| Some((var.to_string(), value?)))
+        .collect()
+    }
+}
+
+/// Find a command in `PATH`, as the shell would
+///
+/// Commands given as a path are returned as-is if they exist.
+pub fn find_in_path(command: &str) -> Option<PathBuf> {
+    let command_path = Path::new(command);
+    if command_path.components().count() > 1 {
+        return command_path.is_file().then(|| command_path.to_path_buf());
+    }
+
+    let file_name = format!("{}{}", command, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Check if an acceptable LLVM version is installed (REQUIRED for Zirco)
+///
+/// Candidates are probed for each acceptable major version in order, and the
+/// first match is returned.
+pub fn check_llvm(llvm_versions: &[String]) -> Result<Llvm, Box<dyn std::error::Error>> {
+    let llvm_versions_desc = llvm_versions_desc(llvm_versions);
+
+    // An explicit override is used exactly, without falling back to the candidates
+    if let Some(llvm_config) = std::env::var_os("ZIRCON_LLVM_CONFIG") {
+        let llvm_config = llvm_config.to_string_lossy();
+        let output = Command::new(llvm_config.as_ref())
+            .arg("--version")
+            .output()
+            .map_err(|e| {
+                format!(
+                    "ZIRCON_LLVM_CONFIG is set to '{}', but it could not be run: {}",
+                    llvm_config, e
+                )
+            })?;
+
+        let version_str = String::from_utf8_lossy(&output.stdout);
+        let version = version_str.trim();
+        if !output.status.success() || !is_acceptable_llvm_version(version, llvm_versions) {
+            return Err(format!(
+                "ZIRCON_LLVM_CONFIG points to '{}' (version '{}'), but Zirco requires {}",
+                llvm_config, version, llvm_versions_desc
+            )
+            .into());
+        }
+
+        return Ok(Llvm::new(&llvm_config, version));
+    }
 
-/// Check if LLVM 20 is installed (REQUIRED for Zirco)
-pub fn check_llvm() -> Result<String, Box<dyn std::error::Error>> {
     // List of possible llvm-config command names to try
-    let llvm_config_candidates = [
+    let mut llvm_config_candidates = vec![
         // Direct command
-        "llvm-config",
-        // Version-suffixed (common on Ubuntu/Debian)
-        &format!("llvm-config-{}", config::REQUIRED_LLVM_VERSION),
-        // MacPorts prefix
-        &format!("llvm-config-mp-{}", config::REQUIRED_LLVM_VERSION),
-        // Homebrew paths (Intel Mac)
-        &format!(
-            "/usr/local/opt/llvm@{}/bin/llvm-config",
-            config::REQUIRED_LLVM_VERSION
-        ),
-        "/usr/local/opt/llvm/bin/llvm-config",
-        // Homebrew paths (Apple Silicon Mac)
-        &format!(
-            "/opt/homebrew/opt/llvm@{}/bin/llvm-config",
-            config::REQUIRED_LLVM_VERSION
-        ),
-        "/opt/homebrew/opt/llvm/bin/llvm-config",
+        "llvm-config".to_string(),
     ];
+    for llvm_version in llvm_versions {
+        llvm_config_candidates.extend([
+            // Version-suffixed (common on Ubuntu/Debian)
+            format!("llvm-config-{}", llvm_version),
+            // MacPorts prefix
+            format!("llvm-config-mp-{}", llvm_version),
+            // Homebrew paths (Intel Mac)
+            format!("/usr/local/opt/llvm@{}/bin/llvm-config", llvm_version),
+            // Homebrew paths (Apple Silicon Mac)
+            format!("/opt/homebrew/opt/llvm@{}/bin/llvm-config", llvm_version),
+        ]);
+    }
+    llvm_config_candidates.extend([
+        // Unversioned Homebrew paths (Intel and Apple Silicon Mac)
+        "/usr/local/opt/llvm/bin/llvm-config".to_string(),
+        "/opt/homebrew/opt/llvm/bin/llvm-config".to_string(),
+    ]);
 
     for cmd in &llvm_config_candidates {
+        verbose!("Trying {} --version", cmd);
         let output = Command::new(cmd).arg("--version").output();
 
         if let Ok(output) = output
@@ -37,18 +202,18 @@ pub fn check_llvm() -> Result<String, Box<dyn std::error::Error>> {
             let version_str = String::from_utf8_lossy(&output.stdout);
             let version = version_str.trim();
 
-            // Check if it's LLVM 20.x.x
-            if version.starts_with(&format!("{}.", config::REQUIRED_LLVM_VERSION)) {
-                return Ok(version.to_string());
+            // Check if it's one of the acceptable major versions
+            if is_acceptable_llvm_version(version, llvm_versions) {
+                return Ok(Llvm::new(cmd, version));
             }
 
-            // If we found LLVM but it's not version 20, warn about it
+            // If we found LLVM but it's not an acceptable version, warn about it
             if !version.is_empty() {
-                eprintln!(
+                warning!(
                     "⚠ Found LLVM {} at '{}', but Zirco requires {}",
                     version,
                     cmd,
-                    config::LLVM_VERSION_DESC
+                    llvm_versions_desc
                 );
             }
         }
@@ -56,37 +221,71 @@ pub fn check_llvm() -> Result<String, Box<dyn std::error::Error>> {
 
     Err(format!(
         "{} not found. Zirco REQUIRES {} specifically.\n  Consider using `llvmenv` to compile an appropriate version of LLVM.\n  Please note that the LLVM binary distributions are NOT supported due to missing components.",
-        config::LLVM_VERSION_DESC,
-        config::LLVM_VERSION_DESC,
+        llvm_versions_desc, llvm_versions_desc,
     )
     .into())
 }
 
 /// Check if clang is installed (REQUIRED for Zirco)
-pub fn check_clang() -> Result<String, Box<dyn std::error::Error>> {
+pub fn check_clang(llvm_versions: &[String]) -> Result<Clang, Box<dyn std::error::Error>> {
+    // An explicit override is used exactly, without falling back to the candidates
+    if let Some(clang) = std::env::var_os("ZIRCON_CLANG") {
+        let clang = clang.to_string_lossy();
+        let output = Command::new(clang.as_ref())
+            .arg("--version")
+            .output()
+            .map_err(|e| {
+                format!(
+                    "ZIRCON_CLANG is set to '{}', but it could not be run: {}",
+                    clang, e
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(format!("ZIRCON_CLANG points to '{}', but it failed to run", clang).into());
+        }
+
+        let version = String::from_utf8_lossy(&output.stdout);
+        let version_line = version.lines().next().unwrap_or("unknown");
+        let acceptable = clang_version_number(version_line)
+            .is_some_and(|number| is_acceptable_llvm_version(number, llvm_versions));
+        if !acceptable {
+            return Err(format!(
+                "ZIRCON_CLANG points to '{}' ({}), but Zirco requires the clang from {}",
+                clang,
+                version_line,
+                llvm_versions_desc(llvm_versions)
+            )
+            .into());
+        }
+        return Ok(Clang::new(&clang, version_line));
+    }
+
     // List of possible clang command names to try
-    let clang_candidates = [
+    let mut clang_candidates = vec![
         // Direct command
-        "clang",
-        // Version-suffixed (common on Ubuntu/Debian)
-        &format!("clang-{}", config::REQUIRED_LLVM_VERSION),
-        // MacPorts prefix
-        &format!("clang-mp-{}", config::REQUIRED_LLVM_VERSION),
-        // Homebrew paths (Intel Mac)
-        &format!(
-            "/usr/local/opt/llvm@{}/bin/clang",
-            config::REQUIRED_LLVM_VERSION
-        ),
-        "/usr/local/opt/llvm/bin/clang",
-        // Homebrew paths (Apple Silicon Mac)
-        &format!(
-            "/opt/homebrew/opt/llvm@{}/bin/clang",
-            config::REQUIRED_LLVM_VERSION
-        ),
-        "/opt/homebrew/opt/llvm/bin/clang",
+        "clang".to_string(),
     ];
+    for llvm_version in llvm_versions {
+        clang_candidates.extend([
+            // Version-suffixed (common on Ubuntu/Debian)
+            format!("clang-{}", llvm_version),
+            // MacPorts prefix
+            format!("clang-mp-{}", llvm_version),
+            // Homebrew paths (Intel Mac)
+            format!("/usr/local/opt/llvm@{}/bin/clang", llvm_version),
+            // Homebrew paths (Apple Silicon Mac)
+            format!("/opt/homebrew/opt/llvm@{}/bin/clang", llvm_version),
+        ]);
+    }
+    clang_candidates.extend([
+        // Unversioned Homebrew paths (Intel and Apple Silicon Mac)
+        "/usr/local/opt/llvm/bin/clang".to_string(),
+        "/opt/homebrew/opt/llvm/bin/clang".to_string(),
+    ]);
 
     for cmd in &clang_candidates {
+        verbose!("Trying {} --version", cmd);
         let output = Command::new(cmd).arg("--version").output();
 
         if let Ok(output) = output
@@ -95,34 +294,149 @@ pub fn check_clang() -> Result<String, Box<dyn std::error::Error>> {
             let version = String::from_utf8_lossy(&output.stdout);
             // Extract just the version line
             let version_line = version.lines().next().unwrap_or("unknown");
-            return Ok(version_line.to_string());
+            return Ok(Clang::new(cmd, version_line));
         }
     }
 
     Err("clang not found. Please install clang".into())
 }
 
-/// Check dependencies and return error if LLVM 20 or clang is missing (strict mode for bootstrap and build)
-pub fn check_dependencies_strict() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Checking dependencies...");
+/// Check dependencies and return error if LLVM or clang is missing (strict mode for bootstrap and build)
+///
+/// Returns the LLVM installation and clang that were found.
+pub fn check_dependencies_strict(
+    llvm_versions: &[String],
+) -> Result<(Llvm, Clang), Box<dyn std::error::Error>> {
+    status!("Checking dependencies...");
 
     // LLVM is required - fail if not found
-    match check_llvm() {
-        Ok(version) => println!("✓ {} found: {}", config::LLVM_VERSION_DESC, version),
+    let llvm = match check_llvm(llvm_versions) {
+        Ok(llvm) => {
+            success!("✓ LLVM found: {}", llvm.version);
+            llvm
+        }
         Err(e) => {
-            eprintln!("✗ {}", e);
+            error!("✗ {}", e);
             return Err(e);
         }
-    }
+    };
 
     // Clang is required
-    match check_clang() {
-        Ok(version) => println!("✓ clang found: {}", version),
+    let clang = match check_clang(llvm_versions) {
+        Ok(clang) => {
+            success!("✓ clang found: {}", clang.version);
+            clang
+        }
         Err(e) => {
-            eprintln!("✗ {}", e);
+            error!("✗ {}", e);
             return Err(e);
         }
+    };
+
+    Ok((llvm, clang))
+}
+
+/// Check if git is installed, returning its version
+pub fn check_git() -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("git not found ({}). Please install git", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git --version failed (exit code: {})",
+            output.status.code().unwrap_or(-1)
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Check the tools needed to build Zircon itself, failing if cargo is missing
+///
+/// Zircon doesn't use LLVM, so only cargo and git are checked. git is only
+/// warned about, since checkouts go through libgit2; cargo may still need it
+/// for git dependencies.
+pub fn check_self_build_dependencies(cargo: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    status!("Checking dependencies...");
+
+    if let Err(e) = build::check_cargo(cargo) {
+        error!("✗ {}", e);
+        return Err(e);
+    }
+
+    match check_git() {
+        Ok(version) => success!("✓ git found: {}", version),
+        Err(e) => warning!("⚠ {}", e),
     }
 
     Ok(())
 }
+
+/// Check dependencies, only warning about anything missing (lenient mode for bootstrap)
+///
+/// Zircon itself can be set up without LLVM, but `zircon build` needs it, so
+/// this tells the user what to install before they get that far.
+pub fn warn_dependencies(llvm_versions: &[String]) {
+    status!("Checking dependencies...");
+
+    let mut missing = Vec::new();
+
+    match check_llvm(llvm_versions) {
+        Ok(llvm) => success!("✓ LLVM found: {}", llvm.version),
+        Err(e) => {
+            warning!("⚠ {}", e);
+            missing.push(llvm_versions_desc(llvm_versions));
+        }
+    }
+
+    match check_clang(llvm_versions) {
+        Ok(clang) => success!("✓ clang found: {}", clang.version),
+        Err(e) => {
+            warning!("⚠ {}", e);
+            missing.push("clang".to_string());
+        }
+    }
+
+    if !missing.is_empty() {
+        warning!(
+            "⚠ Install {} before running 'zircon build' (see the README for instructions).",
+            missing.join(" and ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The version number is found in the `clang --version` lines of common
+    /// distributions
+    #[test]
+    fn parses_clang_version_number() {
+        assert_eq!(
+            clang_version_number("clang version 20.1.8 (https://github.com/llvm/llvm-project)"),
+            Some("20.1.8")
+        );
+        assert_eq!(
+            clang_version_number("Ubuntu clang version 18.1.3 (1ubuntu1)"),
+            Some("18.1.3")
+        );
+        assert_eq!(
+            clang_version_number("Homebrew clang version 20.1.0"),
+            Some("20.1.0")
+        );
+        assert_eq!(clang_version_number("unknown"), None);
+    }
+
+    /// Only the acceptable major versions pass, and `2.x` isn't taken for `20`
+    #[test]
+    fn checks_major_version() {
+        let llvm_versions = ["19".to_string(), "20".to_string()];
+        assert!(is_acceptable_llvm_version("20.1.8", &llvm_versions));
+        assert!(is_acceptable_llvm_version("19.0.0", &llvm_versions));
+        assert!(!is_acceptable_llvm_version("18.1.3", &llvm_versions));
+        assert!(!is_acceptable_llvm_version("2.0", &llvm_versions));
+        assert!(!is_acceptable_llvm_version("200.0", &llvm_versions));
+    }
+}
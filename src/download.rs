@@ -0,0 +1,624 @@
+//! Downloading release archives
+
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{
+    NoProxy, Proxy, StatusCode, Url,
+    blocking::{Client, RequestBuilder},
+    header::{CONTENT_LENGTH, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    redirect::Policy,
+};
+
+use crate::output::{self, status, success, verbose, warning};
+use crate::{checksum, config, paths, releases, verify};
+
+/// Maximum number of download attempts before giving up
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled after each failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum number of redirects to follow, matching reqwest's default policy
+const MAX_REDIRECTS: usize = 10;
+
+/// Options controlling how release archives are downloaded
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    /// Require a valid minisign signature for the archive
+    pub verify_signature: bool,
+    /// Proxy URL to use instead of the `HTTP(S)_PROXY` environment variables
+    pub proxy: Option<String>,
+    /// Ignore the download cache and always download the archive
+    pub no_cache: bool,
+    /// Allow downloading over plain HTTP
+    pub allow_insecure: bool,
+}
+
+/// Get the download URL for a release asset of a zirco-lang repository
+///
+/// The base server defaults to GitHub and can be overridden with
+/// `ZIRCON_DIST_SERVER` or the config file. The
+/// `<org>/<repo>/releases/download/<tag>/<file>` layout is preserved so a
+/// mirror can be a simple reverse proxy.
+pub fn release_url(repo: &str, tag: &str, filename: &str) -> Result<String, Box<dyn Error>> {
+    Ok(format!(
+        "{}/zirco-lang/{}/releases/download/{}/{}",
+        dist_server()?,
+        repo,
+        tag,
+        filename
+    ))
+}
+
+/// Get the base URL of the distribution server
+///
+/// `ZIRCON_DIST_SERVER` takes precedence over `dist_server` in the config file.
+fn dist_server() -> Result<String, Box<dyn Error>> {
+    let server = match std::env::var("ZIRCON_DIST_SERVER") {
+        Ok(server) if !server.trim().is_empty() => Some(server),
+        _ => config::Config::load()?.dist_server,
+    };
+
+    Ok(server.map_or_else(
+        || config::DEFAULT_DIST_SERVER.to_string(),
+        |server| server.trim().trim_end_matches('/').to_string(),
+    ))
+}
+
+/// Check whether an archive argument is an `http://` or `https://` URL rather
+/// than a path
+pub fn is_url(archive: &Path) -> bool {
+    archive
+        .to_str()
+        .is_some_and(|archive| archive.starts_with("https://") || archive.starts_with("http://"))
+}
+
+/// Run `f` on a local copy of an archive given as a path or URL
+///
+/// A URL is downloaded into the temporary directory under its own file name,
+/// so the archive type and default toolchain name are derived as for a local
+/// file, and removed again afterwards.
+pub fn with_local_archive<T>(
+    archive: &Path,
+    options: &DownloadOptions,
+    f: impl FnOnce(&Path) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    if !is_url(archive) {
+        return f(archive);
+    }
+
+    let url = archive.to_string_lossy();
+    if !options.allow_insecure {
+        require_https(&url)?;
+    }
+    let filename = Url::parse(&url)
+        .ok()
+        .and_then(|parsed| {
+            parsed
+                .path_segments()?
+                .next_back()
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .ok_or_else(|| format!("Cannot tell the archive's file name from {}", url))?;
+    let temp_file = std::env::temp_dir().join(filename);
+
+    status!("Downloading from: {}", url);
+    download_file(&client(options)?, &url, &temp_file)?;
+
+    let result = f(&temp_file);
+
+    // Clean up the temporary file (best effort)
+    if temp_file.exists()
+        && let Err(e) = fs::remove_file(&temp_file)
+    {
+        warning!("Warning: Failed to clean up temporary file: {}", e);
+    }
+
+    result
+}
+
+/// Get the path a release archive is cached at
+pub fn cache_path(tag: &str, filename: &str) -> PathBuf {
+    paths::download_cache_dir().join(tag).join(filename)
+}
+
+/// Download a release archive and verify it against its `.sha256` sidecar
+///
+/// The sidecar is expected next to the archive in the same release. Releases
+/// without a sidecar are accepted with a warning so older tags still install.
+///
+/// When `verify_signature` is set, the `.sig` minisign signature must also be
+/// present and valid, otherwise the archive is removed and an error returned.
+///
+/// Unless `no_cache` is set, a valid copy at `cache_path` is used instead of
+/// downloading, and successful downloads are stored there.
+pub fn download_release_archive(
+    client: &Client,
+    url: &str,
+    cache_path: &Path,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn Error>> {
+    if !options.allow_insecure {
+        require_https(url)?;
+    }
+    // Fail before downloading anything if there is no key to verify against
+    if options.verify_signature {
+        verify::release_public_key()?;
+    }
+
+    let use_cache = !options.no_cache && cached_archive_is_valid(client, url, cache_path);
+    if use_cache {
+        status!("Using cached download: {}", cache_path.display());
+        fs::copy(cache_path, dest)?;
+    } else {
+        status!("Downloading from: {}", url);
+        download_and_verify_checksum(client, url, dest)?;
+    }
+
+    if options.verify_signature
+        && let Err(e) = verify_release_signature(client, url, dest)
+    {
+        fs::remove_file(dest).ok();
+        return Err(e);
+    }
+
+    if !use_cache
+        && !options.no_cache
+        && let Err(e) = store_in_cache(dest, cache_path)
+    {
+        warning!("⚠ Failed to cache the download: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Check that a download URL uses HTTPS
+///
+/// Binaries fetched over plain HTTP could be swapped in transit, and the
+/// `.sha256` sidecar would be fetched the same way.
+fn require_https(url: &str) -> Result<(), Box<dyn Error>> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid download URL '{}': {}", url, e))?;
+    if parsed.scheme() != "https" {
+        return Err(format!(
+            "Refusing to download {} over {}; only HTTPS is allowed.\nUse an https:// URL (for releases, check ZIRCON_DIST_SERVER and dist_server), or pass --allow-insecure if you trust the network.",
+            url,
+            parsed.scheme()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Check whether a cached archive can be used instead of downloading `url`
+///
+/// The cached file must match the digest recorded when it was stored. If the
+/// release publishes a `.sha256` sidecar the file must also match it, and
+/// otherwise its size must match the remote file, so a re-published tag (such
+/// as `nightly`) is downloaded again. When the server can't be reached, the
+/// cached file is used as-is.
+fn cached_archive_is_valid(client: &Client, url: &str, cache_path: &Path) -> bool {
+    let file_name = cache_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let Some(recorded) = fs::read_to_string(checksum::sidecar_path(cache_path))
+        .ok()
+        .and_then(|contents| checksum::parse_sidecar(&contents, &file_name))
+    else {
+        return false;
+    };
+    let Ok(actual) = checksum::sha256_file(cache_path) else {
+        return false;
+    };
+    if !checksum::digests_match(&recorded, &actual) {
+        verbose!(
+            "Cached {} is corrupt; downloading again",
+            cache_path.display()
+        );
+        return false;
+    }
+
+    match fetch_expected_sha256(client, url) {
+        Ok(Some(expected)) => checksum::digests_match(&expected, &actual),
+        Ok(None) => {
+            let cached_size = fs::metadata(cache_path).map_or(0, |m| m.len());
+            authorize(client.head(url), url)
+                .send()
+                .ok()
+                .filter(|response| response.status().is_success())
+                // `content_length()` is the (empty) body's length for HEAD
+                .and_then(|response| {
+                    response
+                        .headers()
+                        .get(CONTENT_LENGTH)?
+                        .to_str()
+                        .ok()?
+                        .parse::<u64>()
+                        .ok()
+                })
+                .is_none_or(|size| size == cached_size)
+        }
+        Err(e) => {
+            verbose!(
+                "Could not check the cached download against the server: {}",
+                e
+            );
+            true
+        }
+    }
+}
+
+/// Copy a verified download into the cache, with a sidecar recording its digest
+fn store_in_cache(archive: &Path, cache_path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(archive, cache_path)?;
+    checksum::write_sidecar(cache_path)?;
+    Ok(())
+}
+
+/// Download a release archive and check it against the published `.sha256`
+/// sidecar, if there is one
+fn download_and_verify_checksum(
+    client: &Client,
+    url: &str,
+    dest: &Path,
+) -> Result<(), Box<dyn Error>> {
+    download_file(client, url, dest)?;
+
+    match fetch_expected_sha256(client, url)? {
+        Some(expected) => {
+            status!("Verifying SHA256 checksum...");
+            let actual = checksum::sha256_file(dest)?;
+            if !checksum::digests_match(&expected, &actual) {
+                fs::remove_file(dest).ok();
+                return Err(format!(
+                    "SHA256 mismatch for downloaded archive.\n  Expected: {}\n  Actual:   {}\nThe download may be corrupt or tampered with.",
+                    expected, actual
+                )
+                .into());
+            }
+            success!("✓ Checksum verified");
+        }
+        None => {
+            warning!("⚠ No checksum published for this release; skipping verification");
+        }
+    }
+
+    Ok(())
+}
+
+/// Download the `.sig` sidecar for a release archive and verify it
+fn verify_release_signature(
+    client: &Client,
+    url: &str,
+    archive: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let public_key = verify::release_public_key()?;
+
+    let sig_url = format!("{}.sig", url);
+    let signature = fetch_optional_text(client, &sig_url)?.ok_or_else(|| {
+        format!(
+            "Signature verification was requested, but no signature was published at {}",
+            sig_url
+        )
+    })?;
+
+    status!("Verifying signature...");
+    verify::verify_signature(archive, &signature, &public_key)?;
+    success!("✓ Signature verified");
+
+    Ok(())
+}
+
+/// Download a file from a URL to a local path
+///
+/// The body is written to `<dest>.part` and renamed into place once complete.
+/// Failed attempts are retried with exponential backoff, resuming from the
+/// partial file when the server supports HTTP range requests.
+pub fn download_file(client: &Client, url: &str, dest: &Path) -> Result<(), Box<dyn Error>> {
+    verbose!("Downloading {} to {}", url, dest.display());
+
+    let part_path = sidecar_path(dest, "part");
+    let validator_path = sidecar_path(dest, "part.validator");
+
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match try_download(client, url, &part_path, &validator_path) {
+            Ok(()) => break,
+            Err(AttemptError::Fatal(e)) => {
+                fs::remove_file(&part_path).ok();
+                fs::remove_file(&validator_path).ok();
+                return Err(e);
+            }
+            Err(AttemptError::Retryable(e)) if attempt < MAX_ATTEMPTS => {
+                warning!(
+                    "⚠ Download failed ({}); retrying in {}s...",
+                    e,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(AttemptError::Retryable(e)) => {
+                return Err(
+                    format!("Download failed after {} attempts: {}", MAX_ATTEMPTS, e).into(),
+                );
+            }
+        }
+    }
+
+    fs::rename(&part_path, dest)?;
+    fs::remove_file(&validator_path).ok();
+
+    Ok(())
+}
+
+/// Outcome of a failed download attempt
+enum AttemptError {
+    /// The request can never succeed (e.g. HTTP 404); do not retry
+    Fatal(Box<dyn Error>),
+    /// A transient failure worth retrying
+    Retryable(Box<dyn Error>),
+}
+
+/// Make a single download attempt into `part_path`, resuming if possible
+///
+/// The `ETag` (or `Last-Modified`) of the response is stored in
+/// `validator_path` so that a later resume can send `If-Range` and the server
+/// restarts from scratch if the file changed in the meantime.
+fn try_download(
+    client: &Client,
+    url: &str,
+    part_path: &Path,
+    validator_path: &Path,
+) -> Result<(), AttemptError> {
+    let resume_from = fs::metadata(part_path).map_or(0, |m| m.len());
+    let validator = fs::read_to_string(validator_path).ok();
+
+    let mut request = get(client, url);
+    if resume_from > 0
+        && let Some(validator) = &validator
+    {
+        request = request
+            .header(RANGE, format!("bytes={}-", resume_from))
+            .header(IF_RANGE, validator.trim());
+    }
+
+    let mut response = request
+        .send()
+        .map_err(|e| AttemptError::Retryable(e.into()))?;
+    let status = response.status();
+
+    let append = match status {
+        StatusCode::PARTIAL_CONTENT => {
+            status!("Resuming download from byte {}...", resume_from);
+            true
+        }
+        s if s.is_success() => false,
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            fs::remove_file(part_path).ok();
+            fs::remove_file(validator_path).ok();
+            return Err(AttemptError::Retryable(
+                "server rejected the resume request".into(),
+            ));
+        }
+        s if s.is_client_error()
+            && s != StatusCode::REQUEST_TIMEOUT
+            && s != StatusCode::TOO_MANY_REQUESTS =>
+        {
+            return Err(AttemptError::Fatal(
+                format!(
+                    "Failed to download file: HTTP {}. The release may not be available or may not have pre-built binaries for your platform.",
+                    s
+                )
+                .into(),
+            ));
+        }
+        s => return Err(AttemptError::Retryable(format!("HTTP {}", s).into())),
+    };
+
+    if !append {
+        let new_validator = response
+            .headers()
+            .get(ETAG)
+            .or_else(|| response.headers().get(LAST_MODIFIED))
+            .and_then(|v| v.to_str().ok());
+        fs::remove_file(validator_path).ok();
+        if let Some(validator) = new_validator {
+            fs::write(validator_path, validator).map_err(|e| AttemptError::Fatal(e.into()))?;
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(part_path)
+        .map_err(|e| AttemptError::Fatal(e.into()))?;
+
+    let offset = if append { resume_from } else { 0 };
+    let progress = progress_bar(response.content_length().map(|len| len + offset));
+    progress.set_position(offset);
+
+    // Stream the body to disk instead of buffering the whole archive in memory
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = match response.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                progress.abandon();
+                return Err(AttemptError::Retryable(e.into()));
+            }
+        };
+        if let Err(e) = file.write_all(&buffer[..bytes_read]) {
+            progress.abandon();
+            return Err(AttemptError::Fatal(e.into()));
+        }
+        progress.inc(bytes_read as u64);
+    }
+
+    progress.finish_and_clear();
+
+    Ok(())
+}
+
+/// Create a progress bar for a download, or a spinner if the size is unknown
+///
+/// With `--quiet` the bar is hidden.
+#[allow(clippy::literal_string_with_formatting_args)] // indicatif templates
+fn progress_bar(total: Option<u64>) -> ProgressBar {
+    if !output::show_status() {
+        return ProgressBar::hidden();
+    }
+
+    total.map_or_else(
+        || {
+            let spinner = ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{spinner} {bytes} downloaded ({bytes_per_sec})")
+                    .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+            );
+            spinner.enable_steady_tick(Duration::from_millis(100));
+            spinner
+        },
+        |total| {
+            ProgressBar::new(total).with_style(
+                ProgressStyle::with_template(
+                    "{bar:40} {bytes}/{total_bytes} ({bytes_per_sec}, {eta} remaining)",
+                )
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+            )
+        },
+    )
+}
+
+/// Get a path next to `dest` with an extra extension (e.g. `archive.tar.gz.part`)
+fn sidecar_path(dest: &Path, extension: &str) -> PathBuf {
+    let mut path = dest.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Build the HTTP client used for all downloads
+///
+/// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` are honored unless
+/// an explicit proxy is given in `options`. Unless `allow_insecure` is set,
+/// redirects from HTTPS to plain HTTP are refused.
+pub fn client(options: &DownloadOptions) -> Result<Client, Box<dyn Error>> {
+    // The GitHub API rejects requests without a user agent
+    let mut builder = Client::builder().user_agent(concat!("zircon/", env!("CARGO_PKG_VERSION")));
+
+    if !options.allow_insecure {
+        builder = builder.redirect(Policy::custom(|attempt| {
+            let downgrade = attempt.url().scheme() != "https"
+                && attempt
+                    .previous()
+                    .last()
+                    .is_some_and(|url| url.scheme() == "https");
+            if downgrade {
+                let error = format!("refusing to follow a redirect to {}", attempt.url());
+                attempt.error(error)
+            } else if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }));
+    }
+
+    if let Some(proxy_url) = &options.proxy {
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy_url, e))?
+            .no_proxy(NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Hosts that are sent the `GITHUB_TOKEN`, in addition to the configured
+/// GitHub API server
+const GITHUB_HOSTS: &[&str] = &["github.com", "api.github.com"];
+
+/// Start a GET request, authenticating with `GITHUB_TOKEN` if it is set
+///
+/// The token raises GitHub's API rate limits (e.g. in CI). It is only sent to
+/// GitHub, never to mirrors or other servers.
+pub fn get(client: &Client, url: &str) -> RequestBuilder {
+    authorize(client.get(url), url)
+}
+
+/// Add the `GITHUB_TOKEN` to a request for `url` if it goes to GitHub
+fn authorize(request: RequestBuilder, url: &str) -> RequestBuilder {
+    let Some(token) = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+    else {
+        return request;
+    };
+
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+    let api_host = Url::parse(&releases::github_api())
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+    let is_github = host.is_some_and(|host| {
+        GITHUB_HOSTS.contains(&host.as_str()) || Some(&host) == api_host.as_ref()
+    });
+
+    if is_github {
+        request.bearer_auth(token.trim())
+    } else {
+        request
+    }
+}
+
+/// Fetch the expected SHA256 digest of `url` from its `.sha256` sidecar
+///
+/// Returns `None` if the sidecar does not exist (HTTP 404).
+fn fetch_expected_sha256(client: &Client, url: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let sidecar_url = format!("{}.sha256", url);
+    let Some(contents) = fetch_optional_text(client, &sidecar_url)? else {
+        return Ok(None);
+    };
+
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    let expected = checksum::parse_sidecar(&contents, file_name).ok_or_else(|| {
+        format!(
+            "Malformed checksum file at {}: it has no SHA256 digest for {}",
+            sidecar_url, file_name
+        )
+    })?;
+
+    Ok(Some(expected))
+}
+
+/// Fetch a small text file, returning `None` if it does not exist (HTTP 404)
+fn fetch_optional_text(client: &Client, url: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let response = get(client, url).send()?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to download {}: HTTP {}", url, response.status()).into());
+    }
+
+    Ok(Some(response.text()?))
+}
@@ -0,0 +1,38 @@
+//! Parsing of human-friendly durations such as `30d` or `2w`
+
+use std::time::Duration;
+
+/// Parse a duration made of a whole number and a unit suffix
+///
+/// Supported units are `s` (seconds), `m` (minutes), `h` (hours), `d` (days)
+/// and `w` (weeks).
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| invalid_duration(input))?;
+    let (amount, unit) = input.split_at(split);
+
+    let amount: u64 = amount.parse().map_err(|_| invalid_duration(input))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid_duration(input)),
+    };
+
+    amount
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too large", input))
+}
+
+/// Error message for a duration that couldn't be parsed
+fn invalid_duration(input: &str) -> String {
+    format!(
+        "invalid duration '{}': expected a number followed by s, m, h, d or w (e.g. 30d, 2w)",
+        input
+    )
+}
@@ -1,50 +1,146 @@
 //! Git operations for repository management
 
-use git2::{FetchOptions, RemoteCallbacks, Repository, build::RepoBuilder};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use git2::{
+    Cred, CredentialType, Direction, FetchOptions, Object, Oid, Reference, Remote, RemoteCallbacks,
+    Repository, Status, StatusOptions, SubmoduleUpdateOptions,
+    build::{CheckoutBuilder, RepoBuilder},
+};
+
+use crate::output::{self, progress, verbose};
+
+/// Number of commits fetched for shallow clones
+const SHALLOW_DEPTH: i32 = 1;
+
+/// Depth value that asks libgit2 to convert a shallow repository into a full one
+const UNSHALLOW_DEPTH: i32 = i32::MAX;
 
 /// Clone a repository or open an existing one
-pub fn clone_or_open(url: &str, path: &std::path::Path) -> Result<Repository, git2::Error> {
+///
+/// When `shallow` is set, a fresh clone only fetches the tip commit. Existing
+/// repositories are opened as-is.
+pub fn clone_or_open(
+    url: &str,
+    path: &std::path::Path,
+    shallow: bool,
+) -> Result<Repository, git2::Error> {
     if path.exists() {
         // Open existing repository
+        verbose!("Using existing checkout at {}", path.display());
         Repository::open(path)
     } else {
         // Clone with progress reporting
-        let mut callbacks = RemoteCallbacks::new();
-        callbacks.transfer_progress(|stats| {
-            if stats.received_objects() == stats.total_objects() {
-                eprint!(
-                    "\rResolving deltas {}/{}",
-                    stats.indexed_deltas(),
-                    stats.total_deltas()
-                );
-            } else if stats.total_objects() > 0 {
-                eprint!(
-                    "\rReceived {}/{} objects",
-                    stats.received_objects(),
-                    stats.total_objects()
-                );
-            } else {
-                // No progress yet
-            }
-            std::io::Write::flush(&mut std::io::stderr()).ok();
-            true
-        });
-
         let mut fo = FetchOptions::new();
-        fo.remote_callbacks(callbacks);
+        fo.remote_callbacks(remote_callbacks());
+        if shallow {
+            fo.depth(SHALLOW_DEPTH);
+        }
 
-        eprintln!("Cloning {}...", url);
+        progress!(
+            "Cloning {}{}...",
+            url,
+            if shallow { " (shallow)" } else { "" }
+        );
+        verbose!("Cloning into {}", path.display());
         let repo = RepoBuilder::new().fetch_options(fo).clone(url, path)?;
-        eprintln!("\nClone complete");
+        progress!("\nClone complete");
         Ok(repo)
     }
 }
 
 /// Fetch updates from remote
-pub fn fetch(repo: &Repository) -> Result<(), git2::Error> {
+///
+/// Shallow repositories stay shallow when `shallow` is set; a full repository
+/// is never made shallow.
+pub fn fetch(repo: &Repository, shallow: bool) -> Result<(), git2::Error> {
+    // A depth of 0 means unlimited
+    let depth = if shallow && repo.is_shallow() {
+        SHALLOW_DEPTH
+    } else {
+        0
+    };
+    fetch_with_depth(repo, depth)
+}
+
+/// Fetch the full history of a shallow repository
+pub fn unshallow(repo: &Repository) -> Result<(), git2::Error> {
+    progress!("Fetching full history...");
+    fetch_with_depth(repo, UNSHALLOW_DEPTH)
+}
+
+/// Fetch all branches and tags from origin with the given depth (0 = unlimited)
+fn fetch_with_depth(repo: &Repository, depth: i32) -> Result<(), git2::Error> {
     let mut remote = repo.find_remote("origin")?;
 
+    let mut fo = FetchOptions::new();
+    fo.remote_callbacks(remote_callbacks());
+    fo.depth(depth);
+
+    progress!("Fetching updates...");
+    remote.fetch(
+        &[
+            "refs/heads/*:refs/remotes/origin/*",
+            "refs/tags/*:refs/tags/*",
+        ],
+        Some(&mut fo),
+        None,
+    )?;
+    progress!("\nFetch complete");
+    Ok(())
+}
+
+/// List the references of a remote repository without fetching any objects
+///
+/// Returns `(refname, oid)` pairs such as `("refs/heads/main", ...)`.
+pub fn ls_remote(url: &str) -> Result<Vec<(String, Oid)>, git2::Error> {
+    let mut remote = Remote::create_detached(url)?;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(credentials_callback());
+
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+    let refs = connection
+        .list()?
+        .iter()
+        .map(|head| (head.name().to_string(), head.oid()))
+        .collect();
+
+    Ok(refs)
+}
+
+/// Resolve a single branch or tag on a remote repository without fetching
+///
+/// `reference` may be a full refname or a short branch or tag name.
+pub fn resolve_remote_ref(url: &str, reference: &str) -> Result<Oid, git2::Error> {
+    let refs = ls_remote(url)?;
+    let candidates = [
+        reference.to_string(),
+        format!("refs/heads/{}", reference),
+        format!("refs/tags/{}", reference),
+    ];
+
+    candidates
+        .iter()
+        .find_map(|candidate| {
+            refs.iter()
+                .find(|(name, _)| name == candidate)
+                .map(|(_, oid)| *oid)
+        })
+        .ok_or_else(|| {
+            git2::Error::from_str(&format!("Reference '{}' not found on {}", reference, url))
+        })
+}
+
+/// Create remote callbacks that report transfer progress on stderr (unless
+/// `--quiet`) and supply credentials for private repositories
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
     let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(credentials_callback());
+    if !output::show_status() {
+        return callbacks;
+    }
     callbacks.transfer_progress(|stats| {
         if stats.received_objects() == stats.total_objects() {
             eprint!(
@@ -64,62 +160,211 @@ pub fn fetch(repo: &Repository) -> Result<(), git2::Error> {
         std::io::Write::flush(&mut std::io::stderr()).ok();
         true
     });
+    callbacks
+}
 
-    let mut fo = FetchOptions::new();
-    fo.remote_callbacks(callbacks);
+/// Create a credentials callback for authenticating with a remote
+///
+/// Methods are tried in order, each at most once, since libgit2 calls the
+/// callback again after every rejected attempt:
+/// 1. The SSH agent
+/// 2. The default SSH keys (`~/.ssh/id_ed25519`, `~/.ssh/id_rsa`)
+/// 3. Username/password from the configured git credential helper
+fn credentials_callback()
+-> impl FnMut(&str, Option<&str>, CredentialType) -> Result<Cred, git2::Error> {
+    let mut tried_agent = false;
+    let mut untried_keys = dirs::home_dir()
+        .map(|home| {
+            ["id_ed25519", "id_rsa"]
+                .iter()
+                .map(|key| home.join(".ssh").join(key))
+                .filter(|key| key.exists())
+                .collect::<Vec<PathBuf>>()
+        })
+        .unwrap_or_default()
+        .into_iter();
+    let mut tried_helper = false;
+    let mut tried_default = false;
 
-    eprintln!("Fetching updates...");
-    remote.fetch(
-        &[
-            "refs/heads/*:refs/remotes/origin/*",
-            "refs/tags/*:refs/tags/*",
-        ],
-        Some(&mut fo),
-        None,
-    )?;
-    eprintln!("\nFetch complete");
-    Ok(())
+    move |url, username_from_url, allowed| {
+        let username = username_from_url.unwrap_or("git");
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !tried_agent {
+                tried_agent = true;
+                return Cred::ssh_key_from_agent(username);
+            }
+            if let Some(key) = untried_keys.next() {
+                return Cred::ssh_key(username, None, &key, None);
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_helper {
+            tried_helper = true;
+            if let Ok(config) = git2::Config::open_default()
+                && let Ok(cred) = Cred::credential_helper(&config, url, username_from_url)
+            {
+                return Ok(cred);
+            }
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) && !tried_default {
+            tried_default = true;
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str(&format!(
+            "Authentication failed for {}.\n  Tried: ssh-agent, ~/.ssh/id_ed25519, ~/.ssh/id_rsa, and git credential helpers.\n  Make sure your key is loaded (ssh-add) or a credential helper is configured.",
+            url
+        )))
+    }
 }
 
-/// Checkout a specific reference (branch, tag, or commit)
+/// Resolve a reference (branch, tag, or commit) to the commit it names
 ///
 /// This function checks references in the following order:
 /// 1. Remote branch (`refs/remotes/origin/{ref_name}`) - ensures we use latest after fetch
 /// 2. Short name resolution (tags, local branches) - handled by git2
 /// 3. Commit SHA - direct object lookup
-pub fn checkout_ref(repo: &Repository, ref_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (object, reference) = {
-        // First, try as a remote branch to ensure we use the latest fetched version.
-        // This is important because resolve_reference_from_short_name prefers local
-        // branches, which may be outdated even after fetching.
-        let remote_ref = format!("refs/remotes/origin/{}", ref_name);
-        if let Ok(reference) = repo.find_reference(&remote_ref) {
-            let object = reference.peel_to_commit()?.into_object();
-            (object, Some(reference))
-        } else if let Ok(reference) = repo.resolve_reference_from_short_name(ref_name) {
-            // Try short name resolution (handles tags, local branches, etc.)
-            let object = reference.peel_to_commit()?.into_object();
-            (object, Some(reference))
-        } else if let Ok(oid) = git2::Oid::from_str(ref_name) {
-            // Try as a direct commit SHA
-            let object = repo.find_object(oid, None)?;
-            (object, None)
-        } else {
-            return Err(format!("Could not find reference: {}", ref_name).into());
-        }
-    };
+///
+/// Annotated tags are peeled to the commit they point to. The git reference
+/// that was found is returned alongside, unless a SHA was given.
+pub fn resolve_ref<'r>(
+    repo: &'r Repository,
+    ref_name: &str,
+) -> Result<(Object<'r>, Option<Reference<'r>>), Box<dyn Error>> {
+    // First, try as a remote branch to ensure we use the latest fetched version.
+    // This is important because resolve_reference_from_short_name prefers local
+    // branches, which may be outdated even after fetching.
+    // A full `refs/heads/` name refers to the remote's branch too.
+    let branch_name = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+    let remote_ref = format!("refs/remotes/origin/{}", branch_name);
+    if let Ok(reference) = repo.find_reference(&remote_ref) {
+        let object = reference.peel_to_commit()?.into_object();
+        Ok((object, Some(reference)))
+    } else if let Ok(reference) = repo.resolve_reference_from_short_name(ref_name) {
+        // Try short name resolution (handles tags, local branches, etc.)
+        let object = reference.peel_to_commit()?.into_object();
+        Ok((object, Some(reference)))
+    } else if let Ok(oid) = Oid::from_str(ref_name) {
+        // Try as a direct SHA, which may name an annotated tag object
+        // rather than a commit
+        let object = repo.find_object(oid, None)?.peel_to_commit()?.into_object();
+        Ok((object, None))
+    } else {
+        Err(format!("Could not find reference: {}", ref_name).into())
+    }
+}
+
+/// Checkout a specific reference (branch, tag, or commit)
+///
+/// The reference is resolved with [`resolve_ref`], so HEAD always ends up on a
+/// commit.
+///
+/// With `force`, local modifications in the working tree are discarded;
+/// otherwise checkout fails if it would overwrite them.
+pub fn checkout_ref(repo: &Repository, ref_name: &str, force: bool) -> Result<(), Box<dyn Error>> {
+    let (object, reference) = resolve_ref(repo, ref_name)?;
 
-    repo.checkout_tree(&object, None)?;
+    let mut checkout = CheckoutBuilder::new();
+    if force {
+        checkout.force();
+    }
+    repo.checkout_tree(&object, Some(&mut checkout))?;
 
     match reference {
         Some(gref) => repo.set_head(gref.name().ok_or("Invalid reference name")?),
         None => repo.set_head_detached(object.id()),
     }?;
 
-    eprintln!("Checked out: {}", ref_name);
+    update_submodules(repo, force)?;
+
+    progress!("Checked out: {}", ref_name);
+    Ok(())
+}
+
+/// Initialize and update a repository's submodules, and theirs in turn, to the
+/// commits recorded in the checked-out tree
+///
+/// Missing commits are fetched from the submodule's remote. Repositories
+/// without submodules are left untouched.
+fn update_submodules(repo: &Repository, force: bool) -> Result<(), git2::Error> {
+    for mut submodule in repo.submodules()? {
+        verbose!("Updating submodule {}", submodule.path().display());
+
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(remote_callbacks());
+        let mut checkout = CheckoutBuilder::new();
+        if force {
+            checkout.force();
+        }
+        let mut options = SubmoduleUpdateOptions::new();
+        options.fetch(fetch_options).checkout(checkout);
+
+        submodule.update(true, Some(&mut options))?;
+        update_submodules(&submodule.open()?, force)?;
+    }
+
     Ok(())
 }
 
+/// List the tracked files with uncommitted changes in a repository's working tree
+///
+/// Untracked and ignored files (such as build output) are not included.
+pub fn modified_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| !entry.status().is_empty())
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+/// Remove untracked and ignored files from a repository's working tree, like
+/// `git clean -xdf`
+///
+/// Top-level entries named in `keep` (such as `target`) are left alone.
+/// Returns the number of files and directories removed.
+pub fn clean_untracked(repo: &Repository, keep: &[&str]) -> Result<usize, Box<dyn Error>> {
+    let workdir = repo.workdir().ok_or("Repository has no working tree")?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .include_ignored(true)
+        .recurse_untracked_dirs(false)
+        .recurse_ignored_dirs(false);
+
+    let mut removed = 0;
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        if !entry.status().intersects(Status::WT_NEW | Status::IGNORED) {
+            continue;
+        }
+        let Some(relative) = entry.path() else {
+            continue;
+        };
+        let top_level = relative.split('/').next().unwrap_or(relative);
+        if keep.contains(&top_level) {
+            continue;
+        }
+
+        let path = workdir.join(relative);
+        verbose!("Removing {}", path.display());
+        // Directories are reported once, with a trailing slash
+        if relative.ends_with('/') {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
 /// Get the current HEAD commit SHA (short version)
 pub fn get_current_commit_short(repo: &Repository) -> Result<String, git2::Error> {
     let head = repo.head()?;
@@ -128,6 +373,12 @@ pub fn get_current_commit_short(repo: &Repository) -> Result<String, git2::Error
     Ok(oid.to_string()[..8].to_string())
 }
 
+/// Get the full SHA of the current commit
+pub fn get_current_commit(repo: &Repository) -> Result<String, git2::Error> {
+    let commit = repo.head()?.peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
 /// Reference type for better version naming
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RefType {
@@ -139,42 +390,215 @@ pub enum RefType {
     Commit(String),
 }
 
+impl RefType {
+    /// Get the name of the toolchain built from this reference
+    ///
+    /// Tags and commits are used as-is; branches get the short SHA of the
+    /// commit being built (e.g. `main@1a2b3c4d`). Slashes are replaced so
+    /// that `feature/foo` becomes `feature~foo` rather than a nested directory.
+    pub fn toolchain_name(&self, commit_sha: &str) -> String {
+        match self {
+            Self::Tag(tag) => sanitize_ref_name(tag),
+            Self::Branch(branch) => format!("{}@{}", sanitize_ref_name(branch), commit_sha),
+            Self::Commit(commit) => commit.clone(), // No prefix for commits
+        }
+    }
+}
+
+/// Make a branch or tag name usable as a single path component
+///
+/// Git doesn't allow `~` in reference names, so replacing `/` with it can be
+/// undone without ambiguity.
+fn sanitize_ref_name(name: &str) -> String {
+    name.replace('/', "~")
+}
+
+/// Get the branch or tag name a toolchain name was made from by
+/// [`RefType::toolchain_name`], undoing the replacement of slashes
+pub fn ref_name_from_toolchain_name(name: &str) -> String {
+    name.replace('~', "/")
+}
+
 /// Determine the type of reference and get appropriate version name
 pub fn determine_ref_type(repo: &Repository, ref_name: &str) -> RefType {
-    // First, try to find it as a tag
+    // First, try to find it as a tag. Both lightweight and annotated tags
+    // count, as long as they lead to a commit.
     let tag_ref = format!("refs/tags/{}", ref_name);
-    if repo.find_reference(&tag_ref).is_ok() {
-        // It's a tag
+    if let Ok(reference) = repo.find_reference(&tag_ref)
+        && reference.peel_to_commit().is_ok()
+    {
         return RefType::Tag(ref_name.to_string());
     }
 
     // Try as a branch (local or remote)
-    let local_branch = format!("refs/heads/{}", ref_name);
-    let remote_branch = format!("refs/remotes/origin/{}", ref_name);
+    let branch_name = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+    let local_branch = format!("refs/heads/{}", branch_name);
+    let remote_branch = format!("refs/remotes/origin/{}", branch_name);
     if repo.find_reference(&local_branch).is_ok() || repo.find_reference(&remote_branch).is_ok() {
-        return RefType::Branch(ref_name.to_string());
+        return RefType::Branch(branch_name.to_string());
     }
 
-    // Try to parse as commit SHA
-    if let Ok(oid) = git2::Oid::from_str(ref_name)
-        && repo.find_commit(oid).is_ok()
+    // Try to parse as commit SHA (or the SHA of an annotated tag object)
+    if let Ok(oid) = Oid::from_str(ref_name)
+        && let Ok(commit) = repo
+            .find_object(oid, None)
+            .and_then(|object| object.peel_to_commit())
     {
-        // Return short commit hash without "commit-" prefix
-        return RefType::Commit(ref_name[..8.min(ref_name.len())].to_string());
+        // Return short commit hash without "commit-" prefix. This is the
+        // commit's own hash, even if a tag object's SHA was given.
+        return RefType::Commit(commit.id().to_string()[..8].to_string());
     }
 
     // If we can resolve it via short name, check what it resolves to
+    // (e.g. a full refname such as `refs/heads/feature/foo`), naming it by its
+    // short name
     if let Ok(reference) = repo.resolve_reference_from_short_name(ref_name)
         && let Some(ref_name_str) = reference.name()
     {
-        if ref_name_str.starts_with("refs/tags/") {
-            return RefType::Tag(ref_name.to_string());
+        if let Some(tag) = ref_name_str.strip_prefix("refs/tags/") {
+            return RefType::Tag(tag.to_string());
         }
-        if ref_name_str.starts_with("refs/heads/") || ref_name_str.starts_with("refs/remotes/") {
-            return RefType::Branch(ref_name.to_string());
+        if let Some(branch) = ref_name_str
+            .strip_prefix("refs/remotes/origin/")
+            .or_else(|| ref_name_str.strip_prefix("refs/heads/"))
+            .or_else(|| ref_name_str.strip_prefix("refs/remotes/"))
+        {
+            return RefType::Branch(branch.to_string());
         }
     }
 
     // Default to treating it as a branch name
     RefType::Branch(ref_name.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use git2::Signature;
+
+    use super::*;
+
+    /// Commit a file to a repository, on top of HEAD if there is one
+    fn commit_file(repo: &Repository, name: &str, contents: &str) -> Result<Oid, Box<dyn Error>> {
+        let workdir = repo.workdir().ok_or("Repository has no working tree")?;
+        fs::write(workdir.join(name), contents)?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new(name))?;
+        index.write()?;
+
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = Signature::now("Zircon Tests", "tests@zirco.dev")?;
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        Ok(repo.commit(Some("HEAD"), &signature, &signature, name, &tree, &parents)?)
+    }
+
+    /// A repository with two commits, whose first commit is tagged `v0.1.0`
+    /// (lightweight) and `v0.2.0` (annotated)
+    fn tagged_repo(dir: &Path) -> Result<(Repository, Oid, Oid), Box<dyn Error>> {
+        let repo = Repository::init(dir)?;
+        let tagged = commit_file(&repo, "README", "zrc\n")?;
+        {
+            let object = repo.find_object(tagged, None)?;
+            repo.tag_lightweight("v0.1.0", &object, false)?;
+            let signature = Signature::now("Zircon Tests", "tests@zirco.dev")?;
+            repo.tag("v0.2.0", &object, &signature, "Release v0.2.0", false)?;
+        }
+        let tip = commit_file(&repo, "CHANGELOG", "unreleased\n")?;
+        Ok((repo, tagged, tip))
+    }
+
+    /// Both kinds of tag resolve to the tagged commit, not the tag object
+    #[test]
+    fn resolves_lightweight_and_annotated_tags() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let (repo, tagged, _) = tagged_repo(dir.path())?;
+
+        for tag in ["v0.1.0", "v0.2.0"] {
+            let (object, reference) = resolve_ref(&repo, tag)?;
+            assert_eq!(object.id(), tagged, "{}", tag);
+            assert_eq!(
+                reference.as_ref().and_then(Reference::name),
+                Some(format!("refs/tags/{}", tag).as_str())
+            );
+            assert_eq!(
+                determine_ref_type(&repo, tag),
+                RefType::Tag(tag.to_string())
+            );
+        }
+        Ok(())
+    }
+
+    /// The SHA of an annotated tag object resolves to its commit, and names
+    /// the toolchain after the commit
+    #[test]
+    fn resolves_annotated_tag_object_sha() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let (repo, tagged, _) = tagged_repo(dir.path())?;
+        let tag_object = repo.refname_to_id("refs/tags/v0.2.0")?;
+        assert_ne!(tag_object, tagged);
+
+        let (object, reference) = resolve_ref(&repo, &tag_object.to_string())?;
+        assert_eq!(object.id(), tagged);
+        assert!(reference.is_none());
+        assert_eq!(
+            determine_ref_type(&repo, &tag_object.to_string()),
+            RefType::Commit(tagged.to_string()[..8].to_string())
+        );
+        Ok(())
+    }
+
+    /// Checking out an annotated tag puts HEAD on the tagged commit
+    #[test]
+    fn checks_out_annotated_tag() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let (repo, tagged, tip) = tagged_repo(dir.path())?;
+        assert_eq!(get_current_commit(&repo)?, tip.to_string());
+
+        checkout_ref(&repo, "v0.2.0", false)?;
+        assert_eq!(get_current_commit(&repo)?, tagged.to_string());
+        assert!(!dir.path().join("CHANGELOG").exists());
+        Ok(())
+    }
+
+    /// `feature/foo` checks out and names a single, valid toolchain directory
+    /// that doesn't collide with `feature-foo`, and maps back to the branch
+    #[test]
+    fn slashed_branch_names_a_safe_toolchain() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let (repo, tagged, tip) = tagged_repo(dir.path())?;
+        repo.branch("feature/foo", &repo.find_commit(tagged)?, false)?;
+        repo.branch("feature-foo", &repo.find_commit(tip)?, false)?;
+
+        checkout_ref(&repo, "feature/foo", false)?;
+        assert_eq!(get_current_commit(&repo)?, tagged.to_string());
+
+        let sha = get_current_commit_short(&repo)?;
+        let name = determine_ref_type(&repo, "feature/foo").toolchain_name(&sha);
+        assert_eq!(name, format!("feature~foo@{}", sha));
+        crate::toolchains::validate_toolchain_name(&name)?;
+
+        let other = determine_ref_type(&repo, "feature-foo").toolchain_name(&sha);
+        assert_ne!(name, other);
+
+        let branch = name
+            .split_once('@')
+            .map_or(name.as_str(), |(branch, _)| branch);
+        assert_eq!(ref_name_from_toolchain_name(branch), "feature/foo");
+        Ok(())
+    }
+
+    /// Slashed tags are sanitized the same way
+    #[test]
+    fn slashed_tag_names_a_safe_toolchain() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let (repo, tagged, _) = tagged_repo(dir.path())?;
+        repo.tag_lightweight("release/0.3", &repo.find_object(tagged, None)?, false)?;
+
+        let name = determine_ref_type(&repo, "release/0.3").toolchain_name("unused");
+        assert_eq!(name, "release~0.3");
+        assert_eq!(ref_name_from_toolchain_name(&name), "release/0.3");
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Helpers for installing files into Zircon's directories
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::output::{verbose, warning};
+
+/// Recursively copy a directory, creating `dst` if needed
+///
+/// Symlinks inside `src` are followed and their targets copied, so the copy
+/// works on filesystems without symlink support.
+pub fn copy_dir_recursive(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+
+        if src_path.is_dir() {
+            copy_dir_recursive(&src_path, &dst_path)?;
+        } else {
+            fs::copy(&src_path, &dst_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a binary built by cargo into a toolchain's `bin` directory
+fn install_binary(name: &str, target_dir: &Path, toolchain_dir: &Path) -> io::Result<bool> {
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    let src = target_dir.join(&file_name);
+    if !src.is_file() {
+        return Ok(false);
+    }
+
+    let bin_dir = toolchain_dir.join("bin");
+    fs::create_dir_all(&bin_dir)?;
+    verbose!("Installing {} to {}", src.display(), bin_dir.display());
+    fs::copy(&src, bin_dir.join(&file_name))?;
+
+    Ok(true)
+}
+
+/// Install the `zrc` binary from cargo's output directory into a toolchain
+pub fn install_zrc_binary(target_dir: &Path, toolchain_dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !install_binary("zrc", target_dir, toolchain_dir)? {
+        return Err(format!("The build did not produce zrc in {}", target_dir.display()).into());
+    }
+    Ok(())
+}
+
+/// Install the `zircop` linter from cargo's output directory into a toolchain
+///
+/// Older zrc versions don't have zircop, so a missing binary only warns.
+pub fn install_zircop_binary(
+    target_dir: &Path,
+    toolchain_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if !install_binary("zircop", target_dir, toolchain_dir)? {
+        warning!("⚠ The build did not produce zircop; the toolchain won't include it");
+    }
+    Ok(())
+}
+
+/// Copy the standard library headers (`include/`) from a zrc checkout into a
+/// toolchain
+pub fn install_include_files(
+    source_dir: &Path,
+    toolchain_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let include_dir = source_dir.join("include");
+    if !include_dir.is_dir() {
+        warning!(
+            "⚠ No include directory found at {}; the toolchain won't include headers",
+            include_dir.display()
+        );
+        return Ok(());
+    }
+
+    copy_dir_recursive(&include_dir, &toolchain_dir.join("include"))?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Advisory locking so that only one Zircon process modifies the installation at a time
+
+use std::error::Error;
+use std::fs::{self, File, TryLockError};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::paths;
+
+/// An exclusive lock on the Zircon installation
+///
+/// The lock is released when this is dropped, and by the OS if the process
+/// exits in any other way.
+#[derive(Debug)]
+pub struct ZirconLock {
+    /// The locked file, kept open for as long as the lock is held
+    _file: File,
+}
+
+/// Whether `--no-lock` was given
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Skip the lock for the rest of the program (`--no-lock`)
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Acquire the installation lock, failing immediately if another process holds it
+///
+/// Returns `None` without locking anything if `--no-lock` was given.
+pub fn acquire() -> Result<Option<ZirconLock>, Box<dyn Error>> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    let lock_file = paths::lock_file();
+    fs::create_dir_all(paths::zircon_root())?;
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_file)?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(ZirconLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Err(format!(
+            "Another zircon process is running (lock held on {}).\nWait for it to finish, or pass --no-lock if you are sure it isn't.",
+            lock_file.display()
+        )
+        .into()),
+        Err(TryLockError::Error(e)) => {
+            Err(format!("Failed to lock {}: {}", lock_file.display(), e).into())
+        }
+    }
+}
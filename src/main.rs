@@ -51,14 +51,25 @@
 )]
 
 mod build;
+mod checksum;
 mod cli;
 mod cmds;
 mod config;
 mod deps;
+mod download;
+mod duration;
 mod git_utils;
+mod installer;
+mod lock;
+mod manifest;
+mod output;
+mod overrides;
 mod paths;
+mod platform;
+mod releases;
 mod toolchains;
 mod update_check;
+mod verify;
 
 use std::error::Error;
 
@@ -66,10 +77,43 @@ use clap::Parser;
 use cli::{Cli, DispatchCommand, ZirconCommand};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // Shims are links to this binary named after the tool they run
+    if let Some(name) = cmds::shim_cmds::invoked_shim_name() {
+        paths::validate_prefix()?;
+        let args: Vec<_> = std::env::args_os().skip(1).collect();
+        return cmds::shim_cmds::run_shim(&name, &args);
+    }
+
     let cli = Cli::parse();
+    paths::validate_prefix()?;
+
+    output::set_verbosity(if cli.quiet {
+        output::Verbosity::Quiet
+    } else if cli.verbose {
+        output::Verbosity::Verbose
+    } else {
+        output::Verbosity::Normal
+    });
 
     // Check for updates (non-blocking, best effort)
-    update_check::check_for_updates();
+    // Internal commands are run by tooling such as shell completions and must stay quiet and fast,
+    // and `self` commands manage the Zircon checkout the check would fetch into
+    if !matches!(
+        cli.command,
+        ZirconCommand::Internal(_) | ZirconCommand::SelfCmds(_)
+    ) {
+        update_check::check_for_updates();
+    }
+
+    if cli.no_lock {
+        lock::disable();
+    }
+    // Commands that modify the installation hold the lock until they return
+    let _lock = if cli.command.needs_lock() {
+        lock::acquire()?
+    } else {
+        None
+    };
 
     match cli.command {
         ZirconCommand::SelfCmds(self_cmds) => self_cmds.dispatch(),
@@ -77,10 +121,22 @@ fn main() -> Result<(), Box<dyn Error>> {
         ZirconCommand::Install(install_cmd) => install_cmd.dispatch(),
         ZirconCommand::Import(import_cmd) => import_cmd.dispatch(),
         ZirconCommand::Switch(switch_cmd) => switch_cmd.dispatch(),
+        ZirconCommand::Run(run_cmd) => run_cmd.dispatch(),
         ZirconCommand::List(list_cmd) => list_cmd.dispatch(),
+        ZirconCommand::Info(info_cmd) => info_cmd.dispatch(),
+        ZirconCommand::Export(export_cmd) => export_cmd.dispatch(),
+        ZirconCommand::Verify(verify_cmd) => verify_cmd.dispatch(),
         ZirconCommand::Delete(delete_cmd) => delete_cmd.dispatch(),
+        ZirconCommand::Rename(rename_cmd) => rename_cmd.dispatch(),
         ZirconCommand::Prune(prune_cmd) => prune_cmd.dispatch(),
         ZirconCommand::Env(env_cmd) => env_cmd.dispatch(),
+        ZirconCommand::Override(override_cmds) => override_cmds.dispatch(),
+        ZirconCommand::Shims(shims_cmd) => shims_cmd.dispatch(),
+        ZirconCommand::Doctor(doctor_cmd) => doctor_cmd.dispatch(),
+        ZirconCommand::Repair(repair_cmd) => repair_cmd.dispatch(),
+        ZirconCommand::Logs(logs_cmd) => logs_cmd.dispatch(),
+        ZirconCommand::Clean(clean_cmd) => clean_cmd.dispatch(),
+        ZirconCommand::Completions(completions_cmd) => completions_cmd.dispatch(),
         ZirconCommand::Internal(internal_cmds) => internal_cmds.dispatch(),
     }
 }
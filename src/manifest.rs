@@ -0,0 +1,187 @@
+//! Per-toolchain manifests recording where a toolchain came from
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{checksum, platform};
+
+/// Name of the manifest file inside a toolchain directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// How a toolchain was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolchainSource {
+    /// Built from source with `zircon build`
+    Build,
+    /// Imported from an archive with `zircon import`
+    Import,
+    /// Downloaded from a release with `zircon install`
+    Install,
+    /// No (readable) manifest, e.g. toolchains created by older versions of Zircon
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for ToolchainSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Build => "build",
+            Self::Import => "import",
+            Self::Install => "install",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Describe the cargo features a toolchain was built with (e.g.
+/// `foo, bar (without default features)`)
+pub fn describe_features(features: &[String], no_default_features: bool) -> String {
+    let mut list: Vec<&str> = features.iter().map(String::as_str).collect();
+    if no_default_features {
+        if list.is_empty() {
+            list.push("none");
+        }
+        format!("{} (without default features)", list.join(", "))
+    } else {
+        list.insert(0, "default");
+        list.join(", ")
+    }
+}
+
+/// The contents of a toolchain's `manifest.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// How the toolchain was created
+    pub source: ToolchainSource,
+    /// The git reference, release tag or archive the toolchain was created from
+    pub reference: Option<String>,
+    /// The zrc commit the toolchain was built from, if known
+    pub commit: Option<String>,
+    /// The platform the toolchain was created on (e.g. `linux`)
+    pub platform: Option<String>,
+    /// The architecture the toolchain was created on (e.g. `x64`)
+    pub arch: Option<String>,
+    /// When the toolchain was created, in seconds since the Unix epoch
+    pub created_at: Option<u64>,
+    /// SHA256 digest of the archive an imported or installed toolchain was
+    /// extracted from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_sha256: Option<String>,
+    /// Cargo features zrc was built with, in addition to the defaults unless
+    /// `no_default_features` is set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Whether zrc was built without its default cargo features
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+    /// SHA256 digests of the toolchain's binaries, keyed by path relative to
+    /// the toolchain directory (e.g. `bin/zrc`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checksums: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Create a manifest for a toolchain being created now on this host
+    pub fn new(source: ToolchainSource, reference: Option<String>, commit: Option<String>) -> Self {
+        let (platform, arch) = platform::host_platform_and_arch();
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+
+        Self {
+            source,
+            reference,
+            commit,
+            platform: Some(platform),
+            arch: Some(arch),
+            created_at,
+            archive_sha256: None,
+            features: Vec::new(),
+            no_default_features: false,
+            checksums: BTreeMap::new(),
+        }
+    }
+
+    /// Record the checksums of the files in the toolchain's `bin` directory
+    pub fn record_checksums(&mut self, toolchain_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let bin_dir = toolchain_dir.join("bin");
+        if !bin_dir.is_dir() {
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(&bin_dir)? {
+            let entry = entry?;
+            // Only regular files; symlinks are checked through their targets
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = format!("bin/{}", entry.file_name().to_string_lossy());
+            self.checksums
+                .insert(name, checksum::sha256_file(&entry.path())?);
+        }
+
+        Ok(())
+    }
+
+    /// Record the platform and architecture the toolchain's `zrc` was built for
+    ///
+    /// An imported archive may come from another machine, so its binary is a
+    /// better source than the host. If the binary can't be identified, the
+    /// recorded values are kept.
+    pub fn record_binary_platform(&mut self, toolchain_dir: &Path) {
+        let bin_dir = toolchain_dir.join("bin");
+        let detected = ["zrc", "zrc.exe"]
+            .iter()
+            .find_map(|name| platform::detect_binary_platform(&bin_dir.join(name)));
+
+        if let Some((platform, arch)) = detected {
+            self.platform = Some(platform);
+            self.arch = Some(arch);
+        }
+    }
+
+    /// Describe the recorded platform if it doesn't match this host
+    ///
+    /// Returns `None` when they match or nothing was recorded.
+    pub fn host_mismatch(&self) -> Option<String> {
+        let (platform, arch) = self.platform.as_deref().zip(self.arch.as_deref())?;
+        let (host_platform, host_arch) = platform::host_platform_and_arch();
+
+        if platform == host_platform && arch == host_arch {
+            None
+        } else {
+            Some(format!(
+                "it was built for {}-{}, but this host is {}-{}",
+                platform, arch, host_platform, host_arch
+            ))
+        }
+    }
+
+    /// Write the manifest into a toolchain directory
+    pub fn write(&self, toolchain_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(toolchain_dir.join(MANIFEST_FILE), contents)?;
+        Ok(())
+    }
+
+    /// Read the manifest of a toolchain directory
+    ///
+    /// Toolchains without a readable manifest get an empty one with an
+    /// `unknown` source.
+    pub fn read(toolchain_dir: &Path) -> Self {
+        fs::read_to_string(toolchain_dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
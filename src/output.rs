@@ -0,0 +1,158 @@
+//! Verbosity-gated and colored console output
+//!
+//! Command results (toolchain lists, environment scripts, ...) are always
+//! printed with `println!`. Status and progress messages go through the macros
+//! here so `--quiet` and `--verbose` can control them. Warnings and errors are
+//! always shown.
+//!
+//! Success, warning and error lines are colored when the stream they go to is
+//! a terminal and `NO_COLOR` is not set.
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use owo_colors::{AnsiColors, OwoColorize};
+
+/// How much status output to print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only results, warnings and errors (`--quiet`)
+    Quiet,
+    /// Status and progress messages (the default)
+    Normal,
+    /// Also print commands being run and resolved paths (`--verbose`)
+    Verbose,
+}
+
+/// The selected verbosity, stored as its discriminant
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the verbosity for the rest of the program
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Get the selected verbosity
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Whether status and progress messages should be printed
+pub fn show_status() -> bool {
+    verbosity() >= Verbosity::Normal
+}
+
+/// Whether verbose details should be printed
+pub fn show_verbose() -> bool {
+    verbosity() >= Verbosity::Verbose
+}
+
+/// An output stream that may or may not support color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+/// Whether to use color on a stream
+///
+/// Color is disabled when `NO_COLOR` is set to a non-empty value (see
+/// <https://no-color.org>) or the stream isn't a terminal.
+pub fn use_color(stream: Stream) -> bool {
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    match stream {
+        Stream::Stdout => io::stdout().is_terminal(),
+        Stream::Stderr => io::stderr().is_terminal(),
+    }
+}
+
+/// Color text for a stream, leaving it plain if the stream doesn't use color
+pub fn paint(text: impl Display, color: AnsiColors, stream: Stream) -> String {
+    if use_color(stream) {
+        text.color(color).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Print a status message to stdout unless `--quiet` was given
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::output::show_status() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Print a progress message to stderr unless `--quiet` was given
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::output::show_status() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print a detail to stderr only when `--verbose` was given
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::output::show_verbose() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print a success (`✓`) message to stdout in green unless `--quiet` was given
+macro_rules! success {
+    ($($arg:tt)*) => {
+        if $crate::output::show_status() {
+            println!(
+                "{}",
+                $crate::output::paint(
+                    format_args!($($arg)*),
+                    ::owo_colors::AnsiColors::Green,
+                    $crate::output::Stream::Stdout,
+                )
+            );
+        }
+    };
+}
+
+/// Print a warning (`⚠`) to stderr in yellow
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        eprintln!(
+            "{}",
+            $crate::output::paint(
+                format_args!($($arg)*),
+                ::owo_colors::AnsiColors::Yellow,
+                $crate::output::Stream::Stderr,
+            )
+        )
+    };
+}
+
+/// Print an error (`✗`) to stderr in red
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!(
+            "{}",
+            $crate::output::paint(
+                format_args!($($arg)*),
+                ::owo_colors::AnsiColors::Red,
+                $crate::output::Stream::Stderr,
+            )
+        )
+    };
+}
+
+pub(crate) use {error, progress, status, success, verbose, warning};
@@ -0,0 +1,66 @@
+//! Directory-scoped toolchain overrides stored in `overrides.toml`
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// The contents of `overrides.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overrides {
+    /// Toolchain names, keyed by the absolute path of the directory they apply to
+    pub overrides: BTreeMap<String, String>,
+}
+
+impl Overrides {
+    /// Load the overrides file, treating a missing file as having no overrides
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let overrides_file = paths::overrides_file();
+
+        if !overrides_file.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&overrides_file)?;
+        toml::from_str(&contents).map_err(|e| {
+            format!(
+                "Invalid overrides file {}:\n{}",
+                overrides_file.display(),
+                e
+            )
+            .into()
+        })
+    }
+
+    /// Write the overrides file
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(paths::zircon_root())?;
+        fs::write(paths::overrides_file(), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Find the override for `dir` or its nearest ancestor that has one
+    ///
+    /// Returns the directory the override was set for and the toolchain name.
+    pub fn find(&self, dir: &Path) -> Option<(&str, &str)> {
+        dir.ancestors().find_map(|ancestor| {
+            self.overrides
+                .get_key_value(ancestor.to_string_lossy().as_ref())
+                .map(|(dir, version)| (dir.as_str(), version.as_str()))
+        })
+    }
+
+    /// Directories whose override points at a toolchain
+    pub fn dirs_using(&self, version: &str) -> Vec<&str> {
+        self.overrides
+            .iter()
+            .filter(|(_, v)| *v == version)
+            .map(|(dir, _)| dir.as_str())
+            .collect()
+    }
+}
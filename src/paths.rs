@@ -1,15 +1,182 @@
 //! Path management for Zircon directories
 
+use std::error::Error;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-/// Get the Zircon root directory (default: ~/.zircon or %USERPROFILE%\.zircon)
+/// Environment variable that overrides the Zircon root directory
+const PREFIX_ENV_VAR: &str = "ZIRCON_PREFIX";
+
+/// The Zircon root directory, resolved on first use
+static ZIRCON_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the Zircon root directory
 ///
-/// Can be overridden with `ZIRCON_PREFIX` environment variable
+/// In order of precedence:
+/// 1. `ZIRCON_PREFIX`, in which a leading `~` or `~user` is expanded (an
+///    empty value counts as unset)
+/// 2. `$XDG_DATA_HOME/zircon` (default `~/.local/share/zircon`) on Unix
+///    systems other than macOS, unless only `~/.zircon` exists
+/// 3. `~/.zircon` (or `%USERPROFILE%\.zircon`)
+///
+/// The root is resolved once per process, so every path helper agrees on it
+/// even if the environment changes while Zircon runs.
 pub fn zircon_root() -> PathBuf {
-    std::env::var("ZIRCON_PREFIX").map_or_else(
-        |_| dirs::home_dir().map_or_else(|| PathBuf::from(".zircon"), |home| home.join(".zircon")),
-        PathBuf::from,
-    )
+    #[cfg(test)]
+    if let Some(root) = TEST_ROOT.with_borrow(Clone::clone) {
+        return root;
+    }
+
+    ZIRCON_ROOT.get_or_init(resolve_zircon_root).clone()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// A root directory for the current test, taking precedence over
+    /// [`ZIRCON_ROOT`] (which can't be reset once resolved)
+    static TEST_ROOT: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Resets the test root directory when dropped
+#[cfg(test)]
+#[derive(Debug)]
+pub struct TestRootGuard;
+
+#[cfg(test)]
+impl Drop for TestRootGuard {
+    fn drop(&mut self) {
+        TEST_ROOT.set(None);
+    }
+}
+
+/// Point the path helpers at `root` for the rest of the current test
+///
+/// Each test runs on its own thread, so tests using different roots don't
+/// interfere with each other.
+#[cfg(test)]
+pub fn set_test_root(root: &Path) -> TestRootGuard {
+    TEST_ROOT.set(Some(root.to_path_buf()));
+    TestRootGuard
+}
+
+/// Resolve the Zircon root directory from the environment
+fn resolve_zircon_root() -> PathBuf {
+    if let Ok(prefix) = std::env::var(PREFIX_ENV_VAR)
+        && !prefix.is_empty()
+    {
+        return expand_tilde(&prefix);
+    }
+
+    let legacy_root =
+        dirs::home_dir().map_or_else(|| PathBuf::from(".zircon"), |home| home.join(".zircon"));
+    // Existing installations in ~/.zircon keep working where they are
+    match xdg_root() {
+        Some(xdg_root) if xdg_root.exists() || !legacy_root.exists() => xdg_root,
+        _ => legacy_root,
+    }
+}
+
+/// Get the XDG data directory for Zircon on Unix systems other than macOS
+fn xdg_root() -> Option<PathBuf> {
+    if !cfg!(unix) || cfg!(target_os = "macos") {
+        return None;
+    }
+    // Honors XDG_DATA_HOME, falling back to ~/.local/share
+    dirs::data_dir().map(|data_dir| data_dir.join("zircon"))
+}
+
+/// Check that `ZIRCON_PREFIX`, if set, is an absolute path once `~` is expanded
+///
+/// A relative prefix would put toolchains somewhere different depending on
+/// the directory Zircon is run from.
+pub fn validate_prefix() -> Result<(), Box<dyn Error>> {
+    match std::env::var(PREFIX_ENV_VAR) {
+        Ok(prefix) if !prefix.is_empty() => check_prefix(&prefix),
+        _ => Ok(()),
+    }
+}
+
+/// Check that a `ZIRCON_PREFIX` value is an absolute path once `~` is expanded
+fn check_prefix(prefix: &str) -> Result<(), Box<dyn Error>> {
+    if expand_tilde(prefix).is_absolute() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must be an absolute path, but it is '{}'",
+            PREFIX_ENV_VAR, prefix
+        )
+        .into())
+    }
+}
+
+/// Expand a leading `~` (the current user's home directory) or `~user`
+/// (that user's home directory) in a path
+///
+/// Paths that don't start with `~`, or name a user that can't be found, are
+/// returned unchanged.
+fn expand_tilde(path: &str) -> PathBuf {
+    let Some(rest) = path.strip_prefix('~') else {
+        return PathBuf::from(path);
+    };
+    let (user, tail) = rest.split_once(['/', '\\']).unwrap_or((rest, ""));
+
+    let home = if user.is_empty() {
+        dirs::home_dir()
+    } else {
+        user_home_dir(user)
+    };
+    match home {
+        Some(home) if tail.is_empty() => home,
+        Some(home) => home.join(tail),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Look up a user's home directory in `/etc/passwd`
+///
+/// Returns `None` on systems without one, such as Windows.
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() >= 6 && fields[0] == user).then(|| PathBuf::from(fields[5]))
+    })
+}
+
+/// Get the configuration file path
+pub fn config_file() -> PathBuf {
+    zircon_root().join("config.toml")
+}
+
+/// Get the file storing directory-scoped toolchain overrides
+pub fn overrides_file() -> PathBuf {
+    zircon_root().join("overrides.toml")
+}
+
+/// Get the file caching the most recent `zircon install latest` resolution
+pub fn latest_release_cache_file() -> PathBuf {
+    zircon_root().join(".latest_release.json")
+}
+
+/// Get the directory caching downloaded release archives
+pub fn download_cache_dir() -> PathBuf {
+    zircon_root().join("cache").join("downloads")
+}
+
+/// Get the cargo target directory shared by all zrc builds
+pub fn cargo_target_cache_dir() -> PathBuf {
+    zircon_root().join("cache").join("target")
+}
+
+/// Get the directory keeping the logs of failed builds
+pub fn logs_dir() -> PathBuf {
+    zircon_root().join("logs")
+}
+
+/// Get the lock file used to serialize Zircon operations
+pub fn lock_file() -> PathBuf {
+    zircon_root().join("zircon.lock")
 }
 
 /// Get the sources directory
@@ -47,23 +214,12 @@ pub fn current_toolchain_link() -> PathBuf {
     toolchains_dir().join("current")
 }
 
-/// Get the env.sh script path in the current toolchain
-pub fn current_toolchain_env_sh() -> PathBuf {
-    current_toolchain_link().join("env.sh")
-}
-
-/// Get the env.ps1 script path in the current toolchain (for `PowerShell`)
-pub fn current_toolchain_env_ps1() -> PathBuf {
-    current_toolchain_link().join("env.ps1")
-}
-
-/// Get the env.bat script path in the current toolchain (for CMD)
-pub fn current_toolchain_env_bat() -> PathBuf {
-    current_toolchain_link().join("env.bat")
+/// Get the file recording the previously active toolchain (for `zircon switch -`)
+pub fn previous_toolchain_file() -> PathBuf {
+    toolchains_dir().join(".last")
 }
 
-/// Get the self directory (symlink to zircon source)
-#[allow(dead_code)]
+/// Get the self directory (symlink to zircon source, or an unpacked release)
 pub fn self_dir() -> PathBuf {
     zircon_root().join("self")
 }
@@ -84,6 +240,12 @@ pub fn self_zircon_binary() -> PathBuf {
     })
 }
 
+/// Get the backup of the previous Zircon binary, kept by `self` commands that
+/// replace it
+pub fn self_backup_binary() -> PathBuf {
+    zircon_root().join(format!("zircon.old{}", std::env::consts::EXE_SUFFIX))
+}
+
 /// Get the root bin directory
 pub fn bin_dir() -> PathBuf {
     zircon_root().join("bin")
@@ -100,44 +262,233 @@ pub fn zircon_binary_link() -> PathBuf {
 
 /// Ensure all necessary directories exist
 pub fn ensure_directories() -> std::io::Result<()> {
-    std::fs::create_dir_all(sources_dir())?;
-    std::fs::create_dir_all(zirco_lang_dir())?;
-    std::fs::create_dir_all(toolchains_dir())?;
-    std::fs::create_dir_all(self_bin_dir())?;
-    std::fs::create_dir_all(bin_dir())?;
+    for dir in required_directories() {
+        fs::create_dir_all(dir)?;
+    }
     Ok(())
 }
 
+/// Get the directories [`ensure_directories`] creates
+pub fn required_directories() -> [PathBuf; 5] {
+    [
+        sources_dir(),
+        zirco_lang_dir(),
+        toolchains_dir(),
+        self_bin_dir(),
+        bin_dir(),
+    ]
+}
+
 /// Create a symlink or directory junction (Windows) or copy (fallback)
+///
+/// On Unix the new link is created under a temporary name and renamed over
+/// `dst`, so an existing link is replaced atomically and there is never a
+/// moment without one. On filesystems without symlink support `src` is copied
+/// to `dst` instead (see [`read_link`]); that replacement is not atomic.
 #[cfg(unix)]
 pub fn create_link(src: &Path, dst: &Path) -> std::io::Result<()> {
-    // Remove existing link if present
-    if dst.exists() || dst.read_link().is_ok() {
-        if dst.is_dir() && dst.read_link().is_err() {
-            std::fs::remove_dir_all(dst)?;
-        } else {
-            std::fs::remove_file(dst).ok();
-        }
+    // A real directory (or a copy made in place of a link) can't be renamed
+    // over, so it has to go first
+    if dst.is_dir() && dst.read_link().is_err() {
+        fs::remove_dir_all(dst)?;
     }
+    fs::remove_file(copy_marker(dst)).ok();
+
+    let file_name = dst
+        .file_name()
+        .map_or_else(|| "link".into(), |name| name.to_string_lossy().to_string());
+    let temp_link = dst.with_file_name(format!(".{}.tmp-{}", file_name, std::process::id()));
 
-    std::os::unix::fs::symlink(src, dst)
+    // Clean up after a previous run that was interrupted
+    fs::remove_file(&temp_link).ok();
+
+    if let Err(e) = std::os::unix::fs::symlink(src, &temp_link) {
+        if !matches!(
+            e.kind(),
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::Unsupported
+        ) {
+            return Err(e);
+        }
+        if dst.symlink_metadata().is_ok() {
+            fs::remove_file(dst)?;
+        }
+        return create_copy_link(src, dst);
+    }
+    fs::rename(&temp_link, dst).inspect_err(|_| {
+        fs::remove_file(&temp_link).ok();
+    })
 }
 
 /// Create a symlink or directory junction (Windows) or copy (fallback)
+///
+/// Windows can't atomically replace a directory symlink, so this is best
+/// effort: the old link is removed before the new one is created, and an
+/// interruption in between leaves no link. Running the same command again
+/// (e.g. `zircon switch`) recreates it.
+///
+/// Creating symlinks needs Developer Mode or administrator rights. Without
+/// them, directories are linked with a junction instead, and files with a
+/// hard link or, failing that, a copy.
 #[cfg(windows)]
 pub fn create_link(src: &Path, dst: &Path) -> std::io::Result<()> {
     // Remove existing link if present
     if dst.exists() {
         if dst.is_dir() {
-            std::fs::remove_dir_all(dst)?;
+            fs::remove_dir_all(dst)?;
         } else {
-            std::fs::remove_file(dst)?;
+            fs::remove_file(dst)?;
         }
+    } else if dst.symlink_metadata().is_ok() {
+        // A dangling link; directory links have to be removed as directories
+        fs::remove_dir(dst).or_else(|_| fs::remove_file(dst))?;
     }
+    fs::remove_file(copy_marker(dst)).ok();
 
-    if src.is_dir() {
+    let result = if src.is_dir() {
         std::os::windows::fs::symlink_dir(src, dst)
     } else {
         std::os::windows::fs::symlink_file(src, dst)
+    };
+
+    match result {
+        Err(e) if is_symlink_privilege_error(&e) => {
+            if src.is_dir() && create_junction(src, dst).is_ok() {
+                crate::output::status!(
+                    "Symlinks are not permitted; created a directory junction at {} instead",
+                    dst.display()
+                );
+            } else if src.is_file() && fs::hard_link(src, dst).is_ok() {
+                crate::output::status!(
+                    "Symlinks are not permitted; created a hard link at {} instead",
+                    dst.display()
+                );
+            } else {
+                create_copy_link(src, dst)?;
+            }
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+/// Copy `src` to `dst` in place of a link, for filesystems that can't link
+///
+/// A marker file next to `dst` records what was copied so that
+/// [`read_link`] can still resolve it.
+fn create_copy_link(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if src.is_dir() {
+        crate::installer::copy_dir_recursive(src, dst)?;
+    } else {
+        fs::copy(src, dst)?;
+    }
+    fs::write(copy_marker(dst), src.to_string_lossy().as_bytes())?;
+
+    crate::output::status!(
+        "Symlinks are not supported here; copied {} to {} instead (this uses extra disk space)",
+        src.display(),
+        dst.display()
+    );
+    Ok(())
+}
+
+/// Get the marker file recording that `link` is a copy made by [`create_link`]
+fn copy_marker(link: &Path) -> PathBuf {
+    let file_name = link
+        .file_name()
+        .map_or_else(|| "link".into(), |name| name.to_string_lossy().to_string());
+    link.with_file_name(format!(".{}.copy-of", file_name))
+}
+
+/// Get the target of a link made by [`create_link`]
+///
+/// Unlike [`fs::read_link`], this also resolves copies made on
+/// filesystems without symlink support.
+pub fn read_link(link: &Path) -> std::io::Result<PathBuf> {
+    fs::read_link(link).or_else(|e| match fs::read_to_string(copy_marker(link)) {
+        Ok(target) if link.exists() => Ok(PathBuf::from(target)),
+        _ => Err(e),
+    })
+}
+
+/// Whether a failed symlink creation was caused by missing privileges
+#[cfg(windows)]
+fn is_symlink_privilege_error(error: &std::io::Error) -> bool {
+    /// `ERROR_PRIVILEGE_NOT_HELD`, returned when Developer Mode is off
+    const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+
+    error.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD)
+        || error.kind() == std::io::ErrorKind::PermissionDenied
+}
+
+/// Create a directory junction, which unlike a symlink needs no special privileges
+///
+/// The junction starts out as an empty directory, which is removed again if it
+/// can't be turned into a junction.
+#[cfg(windows)]
+fn create_junction(src: &Path, dst: &Path) -> std::io::Result<()> {
+    junction::create(src, dst).inspect_err(|_| {
+        fs::remove_dir(dst).ok();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test root is used while the guard lives, and forgotten afterwards
+    #[test]
+    fn test_root_overrides_root_until_dropped() {
+        let root = PathBuf::from("/zircon-test-root");
+        {
+            let _guard = set_test_root(&root);
+            assert_eq!(zircon_root(), root);
+            assert_eq!(
+                toolchain_dir("v0.1.0"),
+                root.join("toolchains").join("v0.1.0")
+            );
+        }
+        assert_ne!(zircon_root(), root);
+    }
+
+    /// `~` and `~/...` expand to the current user's home directory
+    #[test]
+    fn expands_own_home() -> Result<(), Box<dyn Error>> {
+        let home = dirs::home_dir().ok_or("no home directory")?;
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(
+            expand_tilde("~/work/zircon"),
+            home.join("work").join("zircon")
+        );
+        Ok(())
+    }
+
+    /// `~user` expands to that user's home directory, and is left untouched
+    /// when the user can't be found
+    #[test]
+    fn expands_other_users_home() {
+        assert_eq!(
+            expand_tilde("~zircon-no-such-user/zircon"),
+            PathBuf::from("~zircon-no-such-user/zircon")
+        );
+        if let Some(home) = user_home_dir("root") {
+            assert_eq!(expand_tilde("~root/zircon"), home.join("zircon"));
+        }
+    }
+
+    /// Paths without a leading `~` are used as they are
+    #[test]
+    fn leaves_other_paths_alone() {
+        assert_eq!(expand_tilde("/opt/zircon"), PathBuf::from("/opt/zircon"));
+        assert_eq!(expand_tilde("zircon~1"), PathBuf::from("zircon~1"));
+    }
+
+    /// A prefix must be absolute once expanded
+    #[test]
+    fn rejects_relative_prefix() {
+        assert!(check_prefix("zircon").is_err());
+        assert!(check_prefix("./zircon").is_err());
+        assert!(check_prefix("~zircon-no-such-user/zircon").is_err());
+        assert!(check_prefix("~/zircon").is_ok());
+        assert!(check_prefix(&std::env::temp_dir().display().to_string()).is_ok());
     }
 }
@@ -0,0 +1,180 @@
+//! Host platform detection for release artifacts
+
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Hosts that release artifacts are published for, as Rust's OS and
+/// architecture names (`std::env::consts`) and the architecture name used in
+/// artifact filenames
+///
+/// The OS name doubles as the platform name in artifact filenames. This is
+/// also the list of supported platforms in the README.
+const SUPPORTED_PLATFORMS: &[(&str, &str, &str)] = &[
+    ("linux", "x86_64", "x64"),
+    ("linux", "aarch64", "arm64"),
+    ("macos", "x86_64", "x64"),
+    ("macos", "aarch64", "arm64"),
+    ("windows", "x86_64", "x64"),
+    ("windows", "aarch64", "arm64"),
+    ("freebsd", "x86_64", "x64"),
+    ("freebsd", "aarch64", "arm64"),
+];
+
+/// Detect the current platform and architecture
+///
+/// Returns the names used in release artifact filenames (e.g. `linux`, `x64`).
+pub fn detect_platform_and_arch() -> Result<(String, String), Box<dyn Error>> {
+    let os = std::env::consts::OS;
+    let arch = std::env::consts::ARCH;
+
+    let Some((platform, _, architecture)) = SUPPORTED_PLATFORMS
+        .iter()
+        .find(|(supported_os, supported_arch, _)| *supported_os == os && *supported_arch == arch)
+    else {
+        let supported: Vec<String> = SUPPORTED_PLATFORMS
+            .iter()
+            .map(|(platform, _, arch)| format!("  - {}-{}", platform, arch))
+            .collect();
+        return Err(format!(
+            "Unsupported platform: {} ({}).\nPre-built releases are available for:\n{}",
+            os,
+            arch,
+            supported.join("\n")
+        )
+        .into());
+    };
+
+    Ok(((*platform).to_string(), (*architecture).to_string()))
+}
+
+/// Get the platform and architecture of this host
+///
+/// Hosts without pre-built releases fall back to Rust's names (e.g. `x86`).
+pub fn host_platform_and_arch() -> (String, String) {
+    detect_platform_and_arch().unwrap_or_else(|_| {
+        (
+            std::env::consts::OS.to_string(),
+            std::env::consts::ARCH.to_string(),
+        )
+    })
+}
+
+/// Detect the platform and architecture an executable was built for
+///
+/// Reads the ELF, Mach-O or PE header of the file. Returns `None` for
+/// unreadable files, other formats and architectures without releases.
+pub fn detect_binary_platform(path: &Path) -> Option<(String, String)> {
+    let mut header = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(4096)
+        .read_to_end(&mut header)
+        .ok()?;
+
+    let (platform, arch) = match header.get(..4)? {
+        [0x7f, b'E', b'L', b'F'] => {
+            // e_machine, in the byte order given by EI_DATA
+            let machine = header.get(18..20)?;
+            let machine = if header.get(5) == Some(&2) {
+                u16::from_be_bytes([machine[0], machine[1]])
+            } else {
+                u16::from_le_bytes([machine[0], machine[1]])
+            };
+            // EI_OSABI is 0 (System V) for Linux binaries
+            let platform = if header.get(7) == Some(&9) {
+                "freebsd"
+            } else {
+                "linux"
+            };
+            (platform, elf_arch(machine)?)
+        }
+        // 64-bit little-endian Mach-O
+        [0xcf, 0xfa, 0xed, 0xfe] => {
+            let cpu_type = header.get(4..8)?;
+            let arch =
+                match u32::from_le_bytes([cpu_type[0], cpu_type[1], cpu_type[2], cpu_type[3]]) {
+                    0x0100_0007 => "x64",
+                    0x0100_000c => "arm64",
+                    _ => return None,
+                };
+            ("macos", arch)
+        }
+        [b'M', b'Z', ..] => {
+            let offset = header.get(0x3c..0x40)?;
+            let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize;
+            if header.get(offset..offset + 4)? != b"PE\0\0" {
+                return None;
+            }
+            let machine = header.get(offset + 4..offset + 6)?;
+            let arch = match u16::from_le_bytes([machine[0], machine[1]]) {
+                0x8664 => "x64",
+                0xaa64 => "arm64",
+                _ => return None,
+            };
+            ("windows", arch)
+        }
+        _ => return None,
+    };
+
+    Some((platform.to_string(), arch.to_string()))
+}
+
+/// Map an ELF `e_machine` value to a release architecture name
+const fn elf_arch(machine: u16) -> Option<&'static str> {
+    match machine {
+        0x3e => Some("x64"),
+        0xb7 => Some("arm64"),
+        _ => None,
+    }
+}
+
+/// Get the `<platform>-<arch>` name used in release artifact filenames
+pub fn get_platform_artifact_name() -> Result<String, Box<dyn Error>> {
+    let (platform, arch) = detect_platform_and_arch()?;
+    Ok(format!("{}-{}", platform, arch))
+}
+
+/// Get the archive extension release artifacts use on this platform
+///
+/// Windows releases ship as `.zip`, everything else as `.tar.gz`.
+pub const fn archive_extension() -> &'static str {
+    if cfg!(windows) { "zip" } else { "tar.gz" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The README lists exactly the platforms in [`SUPPORTED_PLATFORMS`]
+    #[test]
+    fn readme_lists_supported_platforms() -> Result<(), Box<dyn Error>> {
+        let readme = include_str!("../README.md");
+        let (_, section) = readme
+            .split_once("## Platform Support")
+            .ok_or("README has no Platform Support section")?;
+        // Rows hold the architecture and the release name as code spans
+        let listed: Vec<(String, String)> = section
+            .lines()
+            .take_while(|line| !line.starts_with("## "))
+            .filter(|line| line.starts_with('|'))
+            .filter_map(|line| {
+                let mut spans = line.split('`').skip(1).step_by(2);
+                Some((spans.next()?.to_string(), spans.next()?.to_string()))
+            })
+            .collect();
+
+        let supported: Vec<(String, String)> = SUPPORTED_PLATFORMS
+            .iter()
+            .map(|(platform, arch, platform_arch)| {
+                (
+                    (*arch).to_string(),
+                    format!("{}-{}", platform, platform_arch),
+                )
+            })
+            .collect();
+        assert_eq!(listed, supported);
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Querying published releases through the GitHub releases API
+
+use std::error::Error;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::blocking::Client;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::output::verbose;
+use crate::{download, paths};
+
+/// Default base URL of the GitHub API
+///
+/// Can be overridden with the `ZIRCON_GITHUB_API` environment variable, e.g.
+/// for GitHub Enterprise.
+const DEFAULT_GITHUB_API: &str = "https://api.github.com";
+
+/// How long a `latest` resolution is reused before asking the API again
+const LATEST_CACHE_TTL: Duration = Duration::from_mins(10);
+
+/// A release of a zirco-lang repository
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    /// The git tag the release was published from
+    pub tag_name: String,
+    /// When the release was published (RFC 3339), if it has been
+    pub published_at: Option<String>,
+    /// Whether the release is marked as a prerelease
+    #[serde(default)]
+    pub prerelease: bool,
+    /// Whether the release is an unpublished draft
+    #[serde(default)]
+    pub draft: bool,
+    /// Files attached to the release
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// A file attached to a release
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    /// The asset's filename
+    pub name: String,
+}
+
+impl Release {
+    /// Whether the release has an asset with the given filename
+    pub fn has_asset(&self, name: &str) -> bool {
+        self.assets.iter().any(|asset| asset.name == name)
+    }
+
+    /// The date part of `published_at` (e.g. `2025-01-31`)
+    pub fn published_date(&self) -> Option<&str> {
+        self.published_at
+            .as_deref()
+            .map(|published_at| published_at.split('T').next().unwrap_or(published_at))
+    }
+}
+
+/// Get the base URL of the GitHub API
+pub fn github_api() -> String {
+    match std::env::var("ZIRCON_GITHUB_API") {
+        Ok(api) if !api.trim().is_empty() => api.trim().trim_end_matches('/').to_string(),
+        _ => DEFAULT_GITHUB_API.to_string(),
+    }
+}
+
+/// Fetch the most recent releases of a zirco-lang repository, newest first
+///
+/// Drafts are skipped.
+pub fn fetch_releases(client: &Client, repo: &str) -> Result<Vec<Release>, Box<dyn Error>> {
+    let url = format!(
+        "{}/repos/zirco-lang/{}/releases?per_page=100",
+        github_api(),
+        repo
+    );
+    verbose!("Fetching {}", url);
+
+    let response = download::get(client, &url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .map_err(|e| format!("Failed to query releases from {}: {}", url, e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to query releases from {}: HTTP {}",
+            url,
+            response.status()
+        )
+        .into());
+    }
+
+    let releases: Vec<Release> = serde_json::from_str(&response.text()?)
+        .map_err(|e| format!("Unexpected response from {}: {}", url, e))?;
+
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .collect())
+}
+
+/// The most recent resolution of `latest`, cached in the Zircon root
+#[derive(Debug, Serialize, Deserialize)]
+struct LatestReleaseCache {
+    /// The base URL of the API the release was looked up through (see
+    /// [`github_api`])
+    api: String,
+    /// The repository the release belongs to
+    repo: String,
+    /// Whether prereleases were considered
+    include_prereleases: bool,
+    /// The resolved tag
+    tag: String,
+    /// When the tag was resolved, in seconds since the Unix epoch
+    resolved_at: u64,
+}
+
+/// Get the seconds since the Unix epoch
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Resolve the tag of the newest release of a zirco-lang repository
+///
+/// Releases are compared by their tags as semantic versions (with an optional
+/// `v` prefix); tags that aren't versions, such as `nightly`, are never
+/// picked. Prereleases are only considered with `include_prereleases`. The
+/// result is cached for a few minutes so repeated runs don't hit the API,
+/// separately for each API base URL.
+pub fn resolve_latest_tag(
+    client: &Client,
+    repo: &str,
+    include_prereleases: bool,
+) -> Result<String, Box<dyn Error>> {
+    let api = github_api();
+    let cache_file = paths::latest_release_cache_file();
+    if let Some(cache) = fs::read_to_string(&cache_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<LatestReleaseCache>(&contents).ok())
+        && cache.api == api
+        && cache.repo == repo
+        && cache.include_prereleases == include_prereleases
+        && now_secs().saturating_sub(cache.resolved_at) < LATEST_CACHE_TTL.as_secs()
+    {
+        verbose!("Using cached latest release {}", cache.tag);
+        return Ok(cache.tag);
+    }
+
+    let releases = fetch_releases(client, repo).map_err(|e| {
+        format!(
+            "Could not determine the latest release: {}\nInstall a specific tag instead (see 'zircon install --list-remote').",
+            e
+        )
+    })?;
+
+    let tag = releases
+        .into_iter()
+        .filter(|release| include_prereleases || !release.prerelease)
+        .filter_map(|release| {
+            let version = Version::parse(
+                release
+                    .tag_name
+                    .strip_prefix('v')
+                    .unwrap_or(&release.tag_name),
+            )
+            .ok()?;
+            (include_prereleases || version.pre.is_empty()).then_some((version, release.tag_name))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag)| tag)
+        .ok_or_else(|| {
+            format!(
+                "No {}releases of {} have a version tag",
+                if include_prereleases { "" } else { "stable " },
+                repo
+            )
+        })?;
+
+    let cache = LatestReleaseCache {
+        api,
+        repo: repo.to_string(),
+        include_prereleases,
+        tag: tag.clone(),
+        resolved_at: now_secs(),
+    };
+    // The cache is only an optimization
+    if fs::create_dir_all(paths::zircon_root()).is_ok() {
+        fs::write(&cache_file, serde_json::to_string_pretty(&cache)?).ok();
+    }
+
+    Ok(tag)
+}
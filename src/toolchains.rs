@@ -1,17 +1,90 @@
 //! Toolchain management operations
 
+use std::cell::OnceCell;
+use std::cmp::Ordering;
 use std::error::Error;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use semver::Version;
+use serde::{Serialize, Serializer};
+
+use crate::manifest::Manifest;
+use crate::output::warning;
+use crate::overrides::Overrides;
 use crate::paths;
 
 /// Information about an installed toolchain
+///
+/// The size and manifest are only read from disk when first asked for, so
+/// listing toolchain names (e.g. for shell completion) stays cheap.
 #[derive(Debug, Clone)]
 pub struct ToolchainInfo {
     /// The version name
     pub name: String,
     /// Whether this is the currently active toolchain
     pub is_current: bool,
+    /// The toolchain directory
+    pub path: PathBuf,
+    /// When the toolchain was installed, in seconds since the Unix epoch
+    /// (taken from the directory's modification time)
+    pub installed_at: Option<u64>,
+    /// Total size of the toolchain's files, once computed
+    size_bytes: OnceCell<u64>,
+    /// Where the toolchain came from, once read
+    manifest: OnceCell<Manifest>,
+}
+
+impl ToolchainInfo {
+    /// Total size of the toolchain's files
+    pub fn size_bytes(&self) -> u64 {
+        *self.size_bytes.get_or_init(|| dir_size(&self.path))
+    }
+
+    /// Where the toolchain came from
+    pub fn manifest(&self) -> &Manifest {
+        self.manifest.get_or_init(|| Manifest::read(&self.path))
+    }
+
+    /// When the toolchain was installed, preferring the manifest over the
+    /// directory's modification time
+    pub fn installed_timestamp(&self) -> Option<u64> {
+        self.manifest().created_at.or(self.installed_at)
+    }
+}
+
+/// The JSON form of a [`ToolchainInfo`], as printed by `zircon list --json`
+#[derive(Serialize)]
+struct ToolchainJson<'a> {
+    /// The version name
+    name: &'a str,
+    /// Whether this is the currently active toolchain
+    is_current: bool,
+    /// The toolchain directory
+    path: &'a Path,
+    /// When the toolchain directory was last modified
+    installed_at: Option<u64>,
+    /// Total size of the toolchain's files
+    size_bytes: u64,
+    /// Where the toolchain came from
+    #[serde(flatten)]
+    manifest: &'a Manifest,
+}
+
+impl Serialize for ToolchainInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ToolchainJson {
+            name: &self.name,
+            is_current: self.is_current,
+            path: &self.path,
+            installed_at: self.installed_at,
+            size_bytes: self.size_bytes(),
+            manifest: self.manifest(),
+        }
+        .serialize(serializer)
+    }
 }
 
 /// List all installed toolchains
@@ -25,7 +98,7 @@ pub fn list_toolchains() -> Result<Vec<ToolchainInfo>, Box<dyn Error>> {
     // Get current toolchain if it exists
     let current_link = paths::current_toolchain_link();
     let current_version = if current_link.exists() {
-        fs::read_link(&current_link)
+        paths::read_link(&current_link)
             .ok()
             .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
     } else {
@@ -35,23 +108,102 @@ pub fn list_toolchains() -> Result<Vec<ToolchainInfo>, Box<dyn Error>> {
     let mut toolchains: Vec<ToolchainInfo> = fs::read_dir(&toolchains_dir)?
         .filter_map(Result::ok)
         .filter(|e| {
-            // Skip the "current" symlink
-            e.file_name() != "current" && e.path().is_dir()
+            // Skip the "current" symlink and hidden entries (e.g. a temporary
+            // link left behind by an interrupted switch)
+            e.file_name() != "current"
+                && !e.file_name().to_string_lossy().starts_with('.')
+                && e.path().is_dir()
         })
         .map(|e| {
             let name = e.file_name().to_string_lossy().to_string();
             let is_current = current_version.as_ref() == Some(&name);
-            ToolchainInfo { name, is_current }
+            read_toolchain_info(name, is_current)
         })
         .collect();
 
-    // Sort by name
-    toolchains.sort_by(|a, b| a.name.cmp(&b.name));
+    // Sort by name, with release versions in version order
+    toolchains.sort_by(|a, b| compare_toolchain_names(&a.name, &b.name));
 
     Ok(toolchains)
 }
 
+/// Order toolchain names for display
+///
+/// Names that are semantic versions (optionally prefixed with `v`, e.g.
+/// `v0.10.0`) come first, in version order, so `v0.2.0` sorts before
+/// `v0.10.0`. Everything else (branches, commits, imported archives) follows
+/// in lexical order.
+pub fn compare_toolchain_names(a: &str, b: &str) -> Ordering {
+    match (parse_version(a), parse_version(b)) {
+        (Some(version_a), Some(version_b)) => version_a.cmp(&version_b).then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// Parse a toolchain name as a semantic version, if it is one
+fn parse_version(name: &str) -> Option<Version> {
+    Version::parse(name.strip_prefix('v').unwrap_or(name)).ok()
+}
+
+/// Get information about a single installed toolchain
+pub fn get_toolchain_info(version: &str) -> Result<ToolchainInfo, Box<dyn Error>> {
+    if !toolchain_exists(version) {
+        return Err(format!(
+            "Toolchain '{}' not found.\nUse 'zircon list' to see installed toolchains.",
+            version
+        )
+        .into());
+    }
+
+    let is_current = get_current_toolchain()?.as_deref() == Some(version);
+    Ok(read_toolchain_info(version.to_string(), is_current))
+}
+
+/// Collect the on-disk information about a toolchain, leaving its size and
+/// manifest to be read when needed
+fn read_toolchain_info(name: String, is_current: bool) -> ToolchainInfo {
+    let path = paths::toolchain_dir(&name);
+    let installed_at = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    ToolchainInfo {
+        name,
+        is_current,
+        path,
+        installed_at,
+        size_bytes: OnceCell::new(),
+        manifest: OnceCell::new(),
+    }
+}
+
+/// Compute the total size of the files in a directory, recursively
+///
+/// Symlinks are counted as links and not followed. Entries that can't be read
+/// are skipped.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|e| match e.path().symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&e.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
 /// Get the currently active toolchain name
+///
+/// A `current` link whose toolchain has been deleted counts as no current
+/// toolchain (see [`get_broken_current_toolchain`]).
 pub fn get_current_toolchain() -> Result<Option<String>, Box<dyn Error>> {
     let current_link = paths::current_toolchain_link();
 
@@ -59,7 +211,7 @@ pub fn get_current_toolchain() -> Result<Option<String>, Box<dyn Error>> {
         return Ok(None);
     }
 
-    let target = fs::read_link(&current_link)?;
+    let target = paths::read_link(&current_link)?;
     let version = target
         .file_name()
         .and_then(|n| n.to_str())
@@ -68,6 +220,176 @@ pub fn get_current_toolchain() -> Result<Option<String>, Box<dyn Error>> {
     Ok(version)
 }
 
+/// Get the toolchain a dangling `current` link points at, if the link exists
+/// but its toolchain doesn't
+pub fn get_broken_current_toolchain() -> Option<String> {
+    let current_link = paths::current_toolchain_link();
+    if current_link.exists() || current_link.symlink_metadata().is_err() {
+        return None;
+    }
+
+    fs::read_link(&current_link)
+        .ok()
+        .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string()))
+}
+
+/// Check that a user-chosen toolchain name is usable as a toolchain directory
+///
+/// Rejects names that would escape the toolchains directory or clash with
+/// Zircon's own entries in it (`current` and hidden files such as `.last`).
+pub fn validate_toolchain_name(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty() {
+        return Err("Toolchain name must not be empty".into());
+    }
+    if name.contains(['/', '\\']) {
+        return Err(format!(
+            "Invalid toolchain name '{}': it must not contain path separators",
+            name
+        )
+        .into());
+    }
+    if name == "current" || name.starts_with('.') {
+        return Err(format!(
+            "Invalid toolchain name '{}': 'current' and names starting with '.' are reserved",
+            name
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Environment variable that selects a toolchain for a single shell, overriding `current`
+pub const TOOLCHAIN_ENV_VAR: &str = "ZIRCON_TOOLCHAIN";
+
+/// Get the toolchain selected with `ZIRCON_TOOLCHAIN`, if any
+///
+/// Errors if the variable isn't a valid toolchain name or names a toolchain
+/// that isn't installed.
+pub fn get_env_toolchain() -> Result<Option<String>, Box<dyn Error>> {
+    let Some(version) = std::env::var(TOOLCHAIN_ENV_VAR)
+        .ok()
+        .filter(|version| !version.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    validate_toolchain_name(&version).map_err(|e| {
+        format!(
+            "{} is set to an unusable toolchain: {}",
+            TOOLCHAIN_ENV_VAR, e
+        )
+    })?;
+    if !toolchain_exists(&version) {
+        return Err(format!(
+            "{} is set to '{}', but that toolchain is not installed.\nUse 'zircon list' to see installed toolchains, or unset {}.",
+            TOOLCHAIN_ENV_VAR, version, TOOLCHAIN_ENV_VAR
+        )
+        .into());
+    }
+
+    Ok(Some(version))
+}
+
+/// Name of the file that pins a directory tree to a toolchain
+pub const PIN_FILE: &str = ".zircon-version";
+
+/// Get the toolchain pinned by a `.zircon-version` file in `dir` or its nearest
+/// ancestor that has one
+///
+/// Errors if the pin isn't a valid toolchain name or the pinned toolchain
+/// isn't installed.
+pub fn get_pinned_toolchain(dir: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    for ancestor in dir.ancestors() {
+        let pin_file = ancestor.join(PIN_FILE);
+        let contents = match fs::read_to_string(&pin_file) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", pin_file.display(), e).into()),
+        };
+
+        let Some(version) = contents.lines().map(str::trim).find(|l| !l.is_empty()) else {
+            continue;
+        };
+        // The pin comes from whatever directory zrc runs in (e.g. a cloned
+        // repository), so it must not reach outside the toolchains directory
+        validate_toolchain_name(version)
+            .map_err(|e| format!("{} pins an unusable toolchain: {}", pin_file.display(), e))?;
+        if !toolchain_exists(version) {
+            return Err(format!(
+                "{} pins toolchain '{}', but it is not installed.\nInstall it, or use 'zircon list' to see installed toolchains.",
+                pin_file.display(),
+                version
+            )
+            .into());
+        }
+        return Ok(Some(version.to_string()));
+    }
+
+    Ok(None)
+}
+
+/// Get the toolchain selected with `zircon override set` for `dir` or its
+/// nearest ancestor that has an override
+///
+/// Errors if the override isn't a valid toolchain name or the toolchain isn't
+/// installed.
+pub fn get_override_toolchain(dir: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let overrides = Overrides::load()?;
+    let Some((override_dir, version)) = overrides.find(dir) else {
+        return Ok(None);
+    };
+
+    validate_toolchain_name(version).map_err(|e| {
+        format!(
+            "The override for {} uses an unusable toolchain: {}\nRemove it with 'zircon override unset' in that directory.",
+            override_dir, e
+        )
+    })?;
+    if !toolchain_exists(version) {
+        return Err(format!(
+            "The override for {} uses toolchain '{}', but it is not installed.\nInstall it, or remove the override with 'zircon override unset' in that directory.",
+            override_dir, version
+        )
+        .into());
+    }
+
+    Ok(Some(version.to_string()))
+}
+
+/// Get the active toolchain for the current directory
+///
+/// An override set with `zircon override set` takes precedence over a
+/// `.zircon-version` pin, then `ZIRCON_TOOLCHAIN`, then `current`.
+pub fn get_active_toolchain() -> Result<Option<String>, Box<dyn Error>> {
+    let dir = std::env::current_dir()?;
+    // Overrides are stored under canonical paths
+    if let Some(version) = get_override_toolchain(&dir.canonicalize()?)? {
+        return Ok(Some(version));
+    }
+    if let Some(version) = get_pinned_toolchain(&dir)? {
+        return Ok(Some(version));
+    }
+    get_env_toolchain()?.map_or_else(get_current_toolchain, |version| Ok(Some(version)))
+}
+
+/// Get the toolchain that was active before the last `zircon switch`, if recorded
+pub fn get_previous_toolchain() -> Result<Option<String>, Box<dyn Error>> {
+    match fs::read_to_string(paths::previous_toolchain_file()) {
+        Ok(contents) => {
+            let version = contents.trim();
+            Ok((!version.is_empty()).then(|| version.to_string()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record the toolchain that was active before a `zircon switch`
+pub fn set_previous_toolchain(version: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(paths::previous_toolchain_file(), format!("{}\n", version))?;
+    Ok(())
+}
+
 /// Delete a specific toolchain
 /// Returns an error if trying to delete the current toolchain
 pub fn delete_toolchain(version: &str) -> Result<(), Box<dyn Error>> {
@@ -89,17 +411,93 @@ pub fn delete_toolchain(version: &str) -> Result<(), Box<dyn Error>> {
 
     fs::remove_dir_all(&toolchain_dir)?;
 
+    // Overrides pointing at the toolchain now fail until they are changed
+    for dir in Overrides::load()?.dirs_using(version) {
+        warning!(
+            "⚠ The override for {} uses the deleted toolchain '{}'",
+            dir,
+            version
+        );
+    }
+
     Ok(())
 }
 
-/// Get list of toolchains that can be pruned (all except current)
-pub fn get_prunable_toolchains() -> Result<Vec<String>, Box<dyn Error>> {
+/// Rename a toolchain, keeping `current` and `zircon switch -` pointing at it
+pub fn rename_toolchain(old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+    if !toolchain_exists(old) {
+        return Err(format!(
+            "Toolchain '{}' not found.\nUse 'zircon list' to see installed toolchains.",
+            old
+        )
+        .into());
+    }
+    validate_toolchain_name(new)?;
+    if toolchain_exists(new) {
+        return Err(format!("Toolchain '{}' already exists.", new).into());
+    }
+
+    let was_current = get_current_toolchain()?.as_deref() == Some(old);
+
+    let new_dir = paths::toolchain_dir(new);
+    fs::rename(paths::toolchain_dir(old), &new_dir)?;
+
+    if was_current {
+        paths::create_link(&new_dir, &paths::current_toolchain_link())?;
+    }
+    if get_previous_toolchain()?.as_deref() == Some(old) {
+        set_previous_toolchain(new)?;
+    }
+
+    let mut overrides = Overrides::load()?;
+    let mut overrides_changed = false;
+    for version in overrides.overrides.values_mut() {
+        if version == old {
+            new.clone_into(version);
+            overrides_changed = true;
+        }
+    }
+    if overrides_changed {
+        overrides.save()?;
+    }
+
+    Ok(())
+}
+
+/// Get list of toolchains that can be pruned
+///
+/// The current toolchain and the `keep` most recently installed toolchains are
+/// never prunable. With `older_than`, only toolchains whose directory was last
+/// modified longer ago than that are prunable.
+pub fn get_prunable_toolchains(
+    keep: usize,
+    older_than: Option<Duration>,
+) -> Result<Vec<String>, Box<dyn Error>> {
     let current = get_current_toolchain()?;
-    let all_toolchains = list_toolchains()?;
+    let mut all_toolchains = list_toolchains()?;
+
+    // A cutoff before the epoch (or before the earliest time the system can
+    // represent) is 0, so nothing counts as older than it
+    let cutoff = older_than.map(|age| {
+        SystemTime::now()
+            .checked_sub(age)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs())
+    });
+
+    // Newest first
+    all_toolchains.sort_by_key(|tc| std::cmp::Reverse(tc.installed_timestamp()));
 
     let prunable: Vec<String> = all_toolchains
         .into_iter()
+        .skip(keep)
         .filter(|tc| Some(&tc.name) != current.as_ref())
+        .filter(|tc| match (cutoff, tc.installed_at) {
+            (None, _) => true,
+            (Some(cutoff), Some(installed_at)) => installed_at < cutoff,
+            // Without a modification time the age is unknown, so keep it
+            (Some(_), None) => false,
+        })
         .map(|tc| tc.name)
         .collect();
 
@@ -110,3 +508,57 @@ pub fn get_prunable_toolchains() -> Result<Vec<String>, Box<dyn Error>> {
 pub fn toolchain_exists(version: &str) -> bool {
     paths::toolchain_dir(version).exists()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Release versions sort numerically ahead of branch and commit names
+    #[test]
+    fn toolchain_name_ordering() {
+        let mut names = vec![
+            "main@abc123",
+            "v0.10.0",
+            "feat-145",
+            "v0.2.0",
+            "0.3.0",
+            "v0.2.0-rc.1",
+            "main@0d4322f7",
+        ];
+        names.sort_by(|a, b| compare_toolchain_names(a, b));
+
+        assert_eq!(
+            names,
+            [
+                "v0.2.0-rc.1",
+                "v0.2.0",
+                "0.3.0",
+                "v0.10.0",
+                "feat-145",
+                "main@0d4322f7",
+                "main@abc123",
+            ]
+        );
+    }
+
+    /// `list_toolchains` returns installed toolchains in that order, skipping
+    /// `current` and hidden entries
+    #[test]
+    fn list_toolchains_sorts_by_version() -> Result<(), Box<dyn Error>> {
+        let root = tempfile::tempdir()?;
+        let _root = paths::set_test_root(root.path());
+        for name in ["main@abc123", "v0.10.0", "v0.2.0", ".v0.3.0.import-1"] {
+            fs::create_dir_all(paths::toolchain_dir(name))?;
+        }
+        paths::create_link(
+            &paths::toolchain_dir("v0.10.0"),
+            &paths::current_toolchain_link(),
+        )?;
+
+        let toolchains = list_toolchains()?;
+        let names: Vec<&str> = toolchains.iter().map(|tc| tc.name.as_str()).collect();
+        assert_eq!(names, ["v0.2.0", "v0.10.0", "main@abc123"]);
+        assert!(toolchains[1].is_current);
+        Ok(())
+    }
+}
@@ -2,31 +2,47 @@
 
 use std::fs;
 use std::path::PathBuf;
+use std::thread;
 use std::time::{Duration, SystemTime};
 
+use crate::output::progress;
+
 /// Check if we should remind the user to update Zircon
 /// Checks once daily if the main branch has moved forward
+///
+/// The check runs on a detached background thread so it never delays the
+/// actual command. If the command finishes first, the check is simply dropped.
 pub fn check_for_updates() {
-    // Don't block or fail on errors - this is just a helpful reminder
-    if let Err(_e) = try_check_for_updates() {
-        // Silently ignore errors in update check
-    }
+    drop(thread::spawn(|| {
+        // Don't fail on errors - this is just a helpful reminder
+        if let Err(_e) = try_check_for_updates() {
+            // Silently ignore errors in update check
+        }
+    }));
 }
 
 /// Internal function that does the actual checking
 fn try_check_for_updates() -> Result<(), Box<dyn std::error::Error>> {
+    // Opt-out for scripts and CI
+    if std::env::var_os("ZIRCON_NO_UPDATE_CHECK").is_some() {
+        return Ok(());
+    }
+
+    let config = crate::config::Config::load()?;
+    if !config.update_check {
+        return Ok(());
+    }
+
     let update_check_file = get_update_check_file()?;
+    let update_interval = Duration::from_hours(config.update_interval_hours);
 
-    // Check if we should check based on last check time (once per day)
+    // Check if we should check based on last check time (once per day by default)
     let should_check = if update_check_file.exists() {
         fs::metadata(&update_check_file)
             .ok()
             .and_then(|metadata| metadata.modified().ok())
             .and_then(|modified| SystemTime::now().duration_since(modified).ok())
-            .is_none_or(|elapsed| {
-                // Check once per day
-                elapsed > Duration::from_secs(24 * 60 * 60)
-            })
+            .is_none_or(|elapsed| elapsed > update_interval)
     } else {
         // First time, create the file and check
         true
@@ -42,27 +58,24 @@ fn try_check_for_updates() -> Result<(), Box<dyn std::error::Error>> {
                 // Get current HEAD commit
                 if let Ok(head) = repo.head()
                     && let Ok(local_commit) = head.peel_to_commit()
+                    && let Ok(origin) = repo.find_remote("origin")
+                    && let Some(url) = origin.url()
+                    // Only ask the remote where main is, without downloading anything
+                    && let Ok(remote_oid) = crate::git_utils::resolve_remote_ref(url, "main")
                 {
                     let local_oid = local_commit.id();
 
-                    // Fetch from origin (silently, don't show errors)
-                    drop(crate::git_utils::fetch(&repo));
-
-                    // Check origin/main
-                    if let Ok(remote_ref) = repo.find_reference("refs/remotes/origin/main")
-                        && let Ok(remote_commit) = remote_ref.peel_to_commit()
-                    {
-                        let remote_oid = remote_commit.id();
-
-                        // Check if remote is ahead
-                        if local_oid != remote_oid {
-                            // Check if local is ancestor of remote (remote is ahead)
-                            if repo.graph_descendant_of(remote_oid, local_oid) == Ok(true) {
-                                eprintln!(
-                                    "💡 Zircon update available! Run 'zircon self update' to update."
-                                );
-                                eprintln!();
-                            }
+                    // Check if remote is ahead
+                    if local_oid != remote_oid {
+                        // A remote commit we don't have yet is newer than anything local;
+                        // otherwise check if local is ancestor of remote (remote is ahead)
+                        let remote_is_ahead = repo.find_commit(remote_oid).is_err()
+                            || repo.graph_descendant_of(remote_oid, local_oid) == Ok(true);
+                        if remote_is_ahead {
+                            progress!(
+                                "💡 Zircon update available! Run 'zircon self update' to update."
+                            );
+                            progress!();
                         }
                     }
                 }
@@ -77,8 +90,13 @@ fn try_check_for_updates() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 /// Get the path to the update check file
+///
+/// The Zircon root is never created here, since the check may still be running
+/// while a command such as `zircon self uninstall` removes it.
 fn get_update_check_file() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let zircon_root = crate::paths::zircon_root();
-    fs::create_dir_all(&zircon_root)?;
+    if !zircon_root.is_dir() {
+        return Err("Zircon root does not exist".into());
+    }
     Ok(zircon_root.join(".last_update_check"))
 }
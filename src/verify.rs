@@ -0,0 +1,50 @@
+//! Signature verification for release archives
+
+use std::error::Error;
+use std::path::Path;
+
+use minisign_verify::{PublicKey, Signature};
+
+use crate::config;
+
+/// Environment variable that overrides the bundled release public key
+const PUBKEY_ENV_VAR: &str = "ZIRCON_RELEASE_PUBKEY";
+
+/// Get the minisign public key used to verify releases
+///
+/// `ZIRCON_RELEASE_PUBKEY` takes precedence over the key bundled at build time.
+pub fn release_public_key() -> Result<String, Box<dyn Error>> {
+    std::env::var(PUBKEY_ENV_VAR)
+        .ok()
+        .or_else(|| config::RELEASE_PUBLIC_KEY.map(ToString::to_string))
+        .ok_or_else(|| {
+            format!(
+                "--verify needs a release public key, and none is bundled with this build of Zircon.\nSet {} to the minisign public key (or the contents of its .pub file) to verify against.",
+                PUBKEY_ENV_VAR
+            )
+            .into()
+        })
+}
+
+/// Verify a minisign signature for an archive
+///
+/// `public_key` may be either the bare base64 key or the contents of a
+/// minisign `.pub` file.
+pub fn verify_signature(
+    archive: &Path,
+    signature: &str,
+    public_key: &str,
+) -> Result<(), Box<dyn Error>> {
+    let public_key = PublicKey::from_base64(public_key.trim())
+        .or_else(|_| PublicKey::decode(public_key))
+        .map_err(|e| format!("Invalid release public key: {}", e))?;
+    let signature =
+        Signature::decode(signature).map_err(|e| format!("Invalid signature file: {}", e))?;
+
+    let content = std::fs::read(archive)?;
+    public_key
+        .verify(&content, &signature, false)
+        .map_err(|e| format!("Signature verification failed: {}", e))?;
+
+    Ok(())
+}